@@ -1,93 +1,854 @@
 use itertools::Itertools;
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 use std::error::Error;
+use std::fmt;
 use std::fmt::Debug;
+use std::time::Instant;
 
+// one bit per item, bit n set for the item with priority n + 1. the first 64 priorities, enough
+// for the puzzle's items, are kept inline so most sets never allocate.
+#[derive(PartialEq, Debug, Clone, Default)]
+struct ItemSet {
+    low: u64,
+    high: Vec<u64>,
+}
+
+impl ItemSet {
+    fn insert(&mut self, priority: usize) {
+        let (word, bit) = ((priority - 1) / 64, (priority - 1) % 64);
+        if word == 0 {
+            self.low |= 1 << bit;
+            return;
+        }
+        if self.high.len() < word {
+            self.high.resize(word, 0);
+        }
+        self.high[word - 1] |= 1 << bit;
+    }
+
+    fn words(&self) -> impl Iterator<Item = &u64> {
+        std::iter::once(&self.low).chain(&self.high)
+    }
+
+    // trailing empty words are dropped so equal sets compare equal
+    fn intersection(&self, other: &ItemSet) -> ItemSet {
+        let mut high: Vec<u64> = self
+            .high
+            .iter()
+            .zip(&other.high)
+            .map(|(a, b)| a & b)
+            .collect();
+        while high.last() == Some(&0) {
+            high.pop();
+        }
+        ItemSet {
+            low: self.low & other.low,
+            high,
+        }
+    }
+
+    fn union(&self, other: &ItemSet) -> ItemSet {
+        let (mut high, shorter) = if self.high.len() >= other.high.len() {
+            (self.high.clone(), &other.high)
+        } else {
+            (other.high.clone(), &self.high)
+        };
+        for (a, b) in high.iter_mut().zip(shorter) {
+            *a |= b;
+        }
+        ItemSet {
+            low: self.low | other.low,
+            high,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.words().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words().all(|w| *w == 0)
+    }
+
+    fn priorities(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words().enumerate().flat_map(|(i, w)| {
+            (0..64)
+                .filter(move |bit| w & (1 << bit) != 0)
+                .map(move |bit| i * 64 + bit + 1)
+        })
+    }
+
+    // the lowest priority in the set
+    fn first(&self) -> Option<usize> {
+        self.words()
+            .enumerate()
+            .find(|(_, w)| **w != 0)
+            .map(|(i, w)| i * 64 + w.trailing_zeros() as usize + 1)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum UnicodeCategory {
+    Alphabetic,
+    Alphanumeric,
+    Lowercase,
+    Uppercase,
+    Numeric,
+}
+
+impl UnicodeCategory {
+    fn contains(&self, c: char) -> bool {
+        match self {
+            UnicodeCategory::Alphabetic => c.is_alphabetic(),
+            UnicodeCategory::Alphanumeric => c.is_alphanumeric(),
+            UnicodeCategory::Lowercase => c.is_lowercase(),
+            UnicodeCategory::Uppercase => c.is_uppercase(),
+            UnicodeCategory::Numeric => c.is_numeric(),
+        }
+    }
+}
+
+// the chars of a unicode category numbered from 1 in code point order. `ranks` holds how many
+// of them come before each block of 64 code points, so priorities stay dense however sparse the
+// category is.
+#[derive(PartialEq, Debug)]
+struct UnicodeItems {
+    category: UnicodeCategory,
+    ranks: Vec<usize>,
+}
+
+impl UnicodeItems {
+    const BLOCK: u32 = 64;
+
+    fn new(category: UnicodeCategory) -> Self {
+        let mut ranks = vec![0; (char::MAX as u32 / Self::BLOCK + 1) as usize];
+        let mut rank = 0;
+        for (block, r) in ranks.iter_mut().enumerate() {
+            *r = rank;
+            rank += Self::block_chars(block)
+                .filter(|c| category.contains(*c))
+                .count();
+        }
+        UnicodeItems { category, ranks }
+    }
+
+    fn block_chars(block: usize) -> impl Iterator<Item = char> {
+        let start = block as u32 * Self::BLOCK;
+        (start..start + Self::BLOCK).filter_map(char::from_u32)
+    }
+
+    fn priority(&self, c: char) -> Option<usize> {
+        if !self.category.contains(c) {
+            return None;
+        }
+        let block = (c as u32 / Self::BLOCK) as usize;
+        let before = Self::block_chars(block)
+            .take_while(|other| *other < c)
+            .filter(|other| self.category.contains(*other))
+            .count();
+        Some(self.ranks[block] + before + 1)
+    }
+
+    fn item(&self, priority: usize) -> Option<char> {
+        let block = self
+            .ranks
+            .partition_point(|r| *r < priority)
+            .checked_sub(1)?;
+        Self::block_chars(block)
+            .filter(|c| self.category.contains(*c))
+            .nth(priority - self.ranks[block] - 1)
+    }
+}
+
+// a-z priority range: 1-26, A-Z priority range: 27-52, 0 for any other ascii char. a lookup
+// avoids branching on the case of every item.
+const AOC_PRIORITIES: [u8; 128] = aoc_priorities();
+
+const fn aoc_priorities() -> [u8; 128] {
+    let mut priorities = [0; 128];
+    let mut i = 0;
+    while i < 26 {
+        priorities[b'a' as usize + i] = i as u8 + 1;
+        priorities[b'A' as usize + i] = i as u8 + 27;
+        i += 1;
+    }
+    priorities
+}
+
+// the valid items of a rucksack and their priorities. every item has a distinct priority from 1
+#[derive(PartialEq, Debug)]
+enum ItemAlphabet {
+    // a-z priority range: 1-26, A-Z priority range: 27-52
+    AdventOfCode,
+    // items in priority order starting at 1
+    Table(Vec<char>),
+    // any char in the category, prioritised by its rank in code point order
+    Unicode(UnicodeItems),
+}
+
+impl ItemAlphabet {
+    // `aoc`, `table:<items>` or `unicode:<category>`
+    fn from_arg(arg: &str) -> Result<Self, Box<dyn Error>> {
+        let alphabet = match arg.split_once(':') {
+            None if arg == "aoc" => ItemAlphabet::AdventOfCode,
+            Some(("table", items)) => {
+                let items: Vec<char> = items.chars().collect();
+                if items.is_empty() || !items.iter().all_unique() {
+                    return Err("an item table needs at least one item and no repeats".into());
+                }
+                ItemAlphabet::Table(items)
+            }
+            Some(("unicode", category)) => {
+                ItemAlphabet::Unicode(UnicodeItems::new(match category {
+                    "alphabetic" => UnicodeCategory::Alphabetic,
+                    "alphanumeric" => UnicodeCategory::Alphanumeric,
+                    "lowercase" => UnicodeCategory::Lowercase,
+                    "uppercase" => UnicodeCategory::Uppercase,
+                    "numeric" => UnicodeCategory::Numeric,
+                    _ => return Err(format!("unknown unicode category {}", category).into()),
+                }))
+            }
+            _ => return Err(format!("unknown item alphabet {}", arg).into()),
+        };
+        Ok(alphabet)
+    }
+
+    fn priority(&self, c: char) -> Option<usize> {
+        match self {
+            ItemAlphabet::AdventOfCode => AOC_PRIORITIES
+                .get(c as usize)
+                .filter(|p| **p != 0)
+                .map(|p| *p as usize),
+            ItemAlphabet::Table(items) => items.iter().position(|i| *i == c).map(|i| i + 1),
+            ItemAlphabet::Unicode(items) => items.priority(c),
+        }
+    }
+
+    fn item(&self, priority: usize) -> Option<char> {
+        match self {
+            ItemAlphabet::AdventOfCode => match priority {
+                1..=26 => Some((b'a' + priority as u8 - 1) as char),
+                27..=52 => Some((b'A' + priority as u8 - 27) as char),
+                _ => None,
+            },
+            ItemAlphabet::Table(items) => items.get(priority.checked_sub(1)?).copied(),
+            ItemAlphabet::Unicode(items) => items.item(priority),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum ItemError {
+    Invalid { item: char, position: usize },
+    OddCount(usize),
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemError::Invalid { item, position } => {
+                write!(
+                    f,
+                    "item {:?} at position {} is not in the alphabet",
+                    item, position
+                )
+            }
+            ItemError::OddCount(count) => {
+                write!(
+                    f,
+                    "{} items can't be split into two equal compartments",
+                    count
+                )
+            }
+        }
+    }
+}
+
+impl Error for ItemError {}
+
+// splits at the middle char rather than the middle byte so multi-byte items stay whole
+fn split_compartments(s: &str) -> Result<(&str, &str), ItemError> {
+    let count = item_count(s);
+    if !count.is_multiple_of(2) {
+        return Err(ItemError::OddCount(count));
+    }
+    if s.is_ascii() {
+        return Ok(s.split_at(count / 2));
+    }
+    let middle = s.char_indices().nth(count / 2).map_or(s.len(), |(i, _)| i);
+    Ok(s.split_at(middle))
+}
+
+fn item_count(s: &str) -> usize {
+    if s.is_ascii() {
+        s.len()
+    } else {
+        s.chars().count()
+    }
+}
+
+fn item_priorities<'a>(
+    items: &'a str,
+    alphabet: &'a ItemAlphabet,
+    offset: usize,
+) -> impl Iterator<Item = Result<usize, ItemError>> + 'a {
+    items.chars().enumerate().map(move |(i, item)| {
+        alphabet.priority(item).ok_or(ItemError::Invalid {
+            item,
+            position: offset + i + 1,
+        })
+    })
+}
+
+fn item_set(items: &str, alphabet: &ItemAlphabet, offset: usize) -> Result<ItemSet, ItemError> {
+    let mut set = ItemSet::default();
+    for priority in item_priorities(items, alphabet, offset) {
+        set.insert(priority?);
+    }
+    Ok(set)
+}
+
+// items are held by their priority in the alphabet the rucksack was read with
 #[derive(PartialEq, Debug, Clone)]
-struct Rucksack(Vec<char>, Vec<char>);
+struct Rucksack(ItemSet, ItemSet);
 
 impl Rucksack {
-    fn from_input(s: &str) -> Self {
-        let middle = s.len() / 2;
-        let chars: Vec<char> = s.chars().collect();
-        Self(chars[..middle].to_vec(), chars[middle..].to_vec())
+    fn from_input(s: &str, alphabet: &ItemAlphabet) -> Result<Self, ItemError> {
+        let (left, right) = split_compartments(s)?;
+        let left_count = item_count(left);
+        Ok(Self(
+            item_set(left, alphabet, 0)?,
+            item_set(right, alphabet, left_count)?,
+        ))
     }
 
-    fn common_item(&self) -> Option<char> {
-        for c in &self.0 {
-            if self.1.contains(c) {
-                return Some(*c);
-            }
+    fn common_item(&self) -> Option<usize> {
+        self.0.intersection(&self.1).first()
+    }
+
+    fn unique_items(&self) -> ItemSet {
+        self.0.union(&self.1)
+    }
+}
+
+fn common_items(rucksacks: &[Rucksack]) -> ItemSet {
+    match rucksacks.split_first() {
+        Some((first, rest)) => rest.iter().fold(first.unique_items(), |set, r| {
+            set.intersection(&r.unique_items())
+        }),
+        None => ItemSet::default(),
+    }
+}
+
+fn common_item(rucksacks: &[Rucksack]) -> Option<usize> {
+    common_items(rucksacks).first()
+}
+
+fn part_one(rucksacks: &[Rucksack]) -> usize {
+    rucksacks.iter().map(|r| r.common_item().unwrap_or(0)).sum()
+}
+
+fn part_two(rucksacks: &[Rucksack]) -> Result<usize, Box<dyn Error>> {
+    badge_sum(rucksacks, 3)
+}
+
+// a shorter last group would report its own items as a badge
+fn check_group_size(rucksacks: usize, group_size: usize) -> Result<(), Box<dyn Error>> {
+    if group_size == 0 {
+        return Err("a group needs at least one rucksack".into());
+    }
+    if !rucksacks.is_multiple_of(group_size) {
+        return Err(format!(
+            "{} rucksacks can't be split into groups of {}",
+            rucksacks, group_size
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// fails naming every group (numbered from 1) that shares no item
+fn badge_sum(rucksacks: &[Rucksack], group_size: usize) -> Result<usize, Box<dyn Error>> {
+    check_group_size(rucksacks.len(), group_size)?;
+    let mut sum = 0;
+    let mut no_badge = vec![];
+    for (i, group) in rucksacks.chunks(group_size).enumerate() {
+        match common_item(group) {
+            Some(badge) => sum += badge,
+            None => no_badge.push(i + 1),
         }
+    }
+    if !no_badge.is_empty() {
+        return Err(format!(
+            "{} groups of {} share no item: {}",
+            no_badge.len(),
+            group_size,
+            no_badge.iter().join(", ")
+        )
+        .into());
+    }
+    Ok(sum)
+}
+
+// partitions all rucksacks into groups of `group_size` sharing exactly one item, returning the
+// indexes of each group. the search is exhaustive so None means no such partition exists.
+fn find_groups(rucksacks: &[Rucksack], group_size: usize) -> Option<Vec<Vec<usize>>> {
+    if group_size == 0 || !rucksacks.len().is_multiple_of(group_size) {
+        return None;
+    }
+    let mut used = vec![false; rucksacks.len()];
+    let mut groups = vec![];
+    if extend_groups(rucksacks, group_size, &mut used, &mut groups) {
+        Some(groups)
+    } else {
         None
     }
+}
+
+fn extend_groups(
+    rucksacks: &[Rucksack],
+    group_size: usize,
+    used: &mut Vec<bool>,
+    groups: &mut Vec<Vec<usize>>,
+) -> bool {
+    // the first unused rucksack has to go in some group, so only groups containing it are tried
+    let Some(first) = used.iter().position(|u| !u) else {
+        return true;
+    };
+    used[first] = true;
+    let mut group = vec![first];
+    if extend_group(
+        rucksacks,
+        group_size,
+        &rucksacks[first].unique_items(),
+        used,
+        &mut group,
+        groups,
+    ) {
+        return true;
+    }
+    used[first] = false;
+    false
+}
+
+fn extend_group(
+    rucksacks: &[Rucksack],
+    group_size: usize,
+    common: &ItemSet,
+    used: &mut Vec<bool>,
+    group: &mut Vec<usize>,
+    groups: &mut Vec<Vec<usize>>,
+) -> bool {
+    if group.len() == group_size {
+        if common.len() != 1 {
+            return false;
+        }
+        groups.push(group.clone());
+        if extend_groups(rucksacks, group_size, used, groups) {
+            return true;
+        }
+        groups.pop();
+        return false;
+    }
+
+    // members are added in increasing index order so each group is only tried once
+    let after = *group.last().unwrap() + 1;
+    for i in after..rucksacks.len() {
+        if used[i] {
+            continue;
+        }
+        let next_common = common.intersection(&rucksacks[i].unique_items());
+        if next_common.is_empty() {
+            continue;
+        }
+        used[i] = true;
+        group.push(i);
+        if extend_group(rucksacks, group_size, &next_common, used, group, groups) {
+            return true;
+        }
+        group.pop();
+        used[i] = false;
+    }
+    false
+}
+
+#[derive(PartialEq, Debug)]
+enum Compartment {
+    Left,
+    Right,
+}
+
+// an item found in both compartments, with the number of copies in each
+#[derive(PartialEq, Debug)]
+struct Duplicate {
+    item: char,
+    left: usize,
+    right: usize,
+}
+
+impl Duplicate {
+    // moving the fewer copies across is the cheapest way to get the item into a single compartment
+    fn repair(&self) -> (usize, Compartment) {
+        if self.left <= self.right {
+            (self.left, Compartment::Right)
+        } else {
+            (self.right, Compartment::Left)
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct DuplicateReport {
+    duplicates: Vec<Duplicate>,
+}
+
+impl DuplicateReport {
+    fn from_input(s: &str, alphabet: &ItemAlphabet) -> Result<Self, ItemError> {
+        let (left, right) = split_compartments(s)?;
+        let left_counts = item_counts(left, alphabet, 0)?;
+        let right_counts = item_counts(right, alphabet, item_count(left))?;
+        let duplicates = left_counts
+            .iter()
+            .filter_map(|(priority, left)| {
+                let right = *right_counts.get(priority)?;
+                Some(Duplicate {
+                    item: alphabet.item(*priority).unwrap(),
+                    left: *left,
+                    right,
+                })
+            })
+            .collect();
+        Ok(DuplicateReport { duplicates })
+    }
+
+    // the puzzle expects exactly one item in both compartments
+    fn is_flagged(&self) -> bool {
+        self.duplicates.len() != 1
+    }
+
+    // item moves needed to make the compartments disjoint; compartment sizes may no longer match
+    fn repair_moves(&self) -> usize {
+        self.duplicates.iter().map(|d| d.repair().0).sum()
+    }
+}
+
+// item counts keyed by priority
+fn item_counts(
+    items: &str,
+    alphabet: &ItemAlphabet,
+    offset: usize,
+) -> Result<BTreeMap<usize, usize>, ItemError> {
+    let mut counts = BTreeMap::new();
+    for priority in item_priorities(items, alphabet, offset) {
+        *counts.entry(priority?).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
 
-    fn unique_items(&self) -> HashSet<char> {
-        let mut chars = HashSet::new();
-        for c in &self.0 {
-            chars.insert(*c);
+fn print_duplicate_reports(input: &str, alphabet: &ItemAlphabet) -> Result<(), ItemError> {
+    let reports = input
+        .lines()
+        .map(|line| DuplicateReport::from_input(line, alphabet))
+        .collect::<Result<Vec<DuplicateReport>, ItemError>>()?;
+    for (i, report) in reports.iter().enumerate().filter(|(_, r)| r.is_flagged()) {
+        let found = report
+            .duplicates
+            .iter()
+            .map(|d| format!("{} ({} left, {} right)", d.item, d.left, d.right))
+            .join(", ");
+        println!(
+            "Rucksack {}: {} duplicates [{}]",
+            i + 1,
+            report.duplicates.len(),
+            found
+        );
+        for d in &report.duplicates {
+            let (moves, to) = d.repair();
+            println!("  move {} {} to the {:?} compartment", moves, d.item, to);
         }
-        for c in &self.1 {
-            chars.insert(*c);
+    }
+    let flagged = reports.iter().filter(|r| r.is_flagged()).count();
+    let moves: usize = reports.iter().map(|r| r.repair_moves()).sum();
+    println!(
+        "{} of {} rucksacks flagged, {} item moves make every rucksack disjoint",
+        flagged,
+        reports.len(),
+        moves
+    );
+    Ok(())
+}
+
+// counts keyed by item priority, or by pairs of priorities with the lower first
+#[derive(PartialEq, Debug, Default)]
+struct Inventory {
+    // rucksacks holding the item
+    frequency: BTreeMap<usize, usize>,
+    // rucksacks holding the item in both compartments
+    duplicates: BTreeMap<usize, usize>,
+    // groups with the item as a badge
+    badges: BTreeMap<usize, usize>,
+    // rucksacks holding both items
+    pairs: BTreeMap<(usize, usize), usize>,
+}
+
+// items that would break the row are quoted, doubling any quote inside
+fn csv_field(item: char) -> String {
+    match item {
+        '"' => "\"\"\"\"".to_string(),
+        ',' | '\n' | '\r' => format!("\"{}\"", item),
+        _ => item.to_string(),
+    }
+}
+
+impl Inventory {
+    fn from_rucksacks(rucksacks: &[Rucksack], group_size: usize) -> Result<Self, Box<dyn Error>> {
+        check_group_size(rucksacks.len(), group_size)?;
+        let mut inventory = Inventory::default();
+        for r in rucksacks {
+            let items: Vec<usize> = r.unique_items().priorities().collect();
+            for (i, a) in items.iter().enumerate() {
+                *inventory.frequency.entry(*a).or_insert(0) += 1;
+                for b in &items[i + 1..] {
+                    *inventory.pairs.entry((*a, *b)).or_insert(0) += 1;
+                }
+            }
+            for p in r.0.intersection(&r.1).priorities() {
+                *inventory.duplicates.entry(p).or_insert(0) += 1;
+            }
         }
+        for group in rucksacks.chunks(group_size) {
+            for p in common_items(group).priorities() {
+                *inventory.badges.entry(p).or_insert(0) += 1;
+            }
+        }
+        Ok(inventory)
+    }
 
-        chars
+    fn to_csv(&self, alphabet: &ItemAlphabet) -> String {
+        let name = |p: &usize| csv_field(alphabet.item(*p).unwrap());
+        let mut csv = "table,item,other,count\n".to_string();
+        for (table, counts) in [
+            ("frequency", &self.frequency),
+            ("duplicates", &self.duplicates),
+            ("badges", &self.badges),
+        ] {
+            for (p, count) in counts {
+                csv.push_str(&format!("{},{},,{}\n", table, name(p), count));
+            }
+        }
+        for ((a, b), count) in &self.pairs {
+            csv.push_str(&format!("pairs,{},{},{}\n", name(a), name(b), count));
+        }
+        csv
+    }
+
+    fn print_tables(&self, alphabet: &ItemAlphabet, top: usize) {
+        let name = |p: &usize| alphabet.item(*p).unwrap();
+        for (title, counts) in [
+            ("Items by rucksacks holding them", &self.frequency),
+            ("Items most often duplicated", &self.duplicates),
+            ("Recurring badges", &self.badges),
+        ] {
+            println!("{}", title);
+            for (p, count) in counts
+                .iter()
+                .sorted_by_key(|(p, c)| (-(**c as i64), **p))
+                .take(top)
+            {
+                println!("  {} {:>6}", name(p), count);
+            }
+        }
+        println!("Items most often together");
+        for ((a, b), count) in self
+            .pairs
+            .iter()
+            .sorted_by_key(|(pair, c)| (-(**c as i64), **pair))
+            .take(top)
+        {
+            println!("  {}{} {:>5}", name(a), name(b), count);
+        }
     }
 }
 
-fn common_item(rucksacks: &[Rucksack]) -> Option<char> {
-    // unique char set of each rucksack sorted by size of set
-    let char_sets: Vec<HashSet<char>> = rucksacks
+fn parse(input: &str, alphabet: &ItemAlphabet) -> Result<Vec<Rucksack>, Box<dyn Error>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            Rucksack::from_input(line, alphabet)
+                .map_err(|e| format!("line {}: {}", i + 1, e).into())
+        })
+        .collect()
+}
+
+// the original char vector implementation, kept as the baseline for `--bench`
+fn scan_common_item(line: &str) -> Option<char> {
+    let chars: Vec<char> = line.chars().collect();
+    let (left, right) = chars.split_at(chars.len() / 2);
+    left.iter().find(|c| right.contains(c)).copied()
+}
+
+fn scan_badge(group: &[&str]) -> Option<char> {
+    let char_sets: Vec<HashSet<char>> = group
         .iter()
-        .map(|r| r.unique_items())
+        .map(|line| line.chars().collect::<HashSet<char>>())
         .sorted_by(|a, b| a.len().partial_cmp(&b.len()).unwrap())
         .collect();
 
-    // iterate over smallest set finding the first char existing in the other sets
     char_sets[0]
         .iter()
         .find(|c| char_sets[1..].iter().all(|set| set.contains(c)))
         .copied()
 }
 
-fn priority(c: char) -> usize {
-    if c.is_ascii_lowercase() {
-        c as usize - 'a' as usize + 1 // a-z priority range: 1-26
-    } else {
-        c as usize - 'A' as usize + 27 // A-Z priority range: 27-52
+// rucksacks with exactly one item shared between the compartments
+fn generate_input(count: usize, seed: u64) -> String {
+    let mut state = seed.max(1);
+    let mut next = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+
+    let mut lines = vec![];
+    for _ in 0..count {
+        let mut items: Vec<char> = (1..=52)
+            .map(|p| ItemAlphabet::AdventOfCode.item(p).unwrap())
+            .collect();
+        for i in (1..items.len()).rev() {
+            items.swap(i, next(i + 1));
+        }
+        let (left_pool, right_pool) = items.split_at(26);
+        let size = 8 + next(24);
+        let left: Vec<char> = (0..size).map(|_| left_pool[next(26)]).collect();
+        let mut right: Vec<char> = (0..size).map(|_| right_pool[next(26)]).collect();
+        right[next(size)] = left[next(size)];
+        lines.push(left.into_iter().chain(right).collect::<String>());
     }
+    lines.join("\n")
 }
 
-fn part_one(rucksacks: &[Rucksack]) -> usize {
-    rucksacks
+fn bench(count: usize) {
+    let input = generate_input(count, 2022);
+    let lines: Vec<&str> = input.lines().collect();
+
+    let alphabet = ItemAlphabet::AdventOfCode;
+    let start = Instant::now();
+    let scan_one: usize = lines
         .iter()
-        .map(|r| r.common_item().unwrap())
-        .map(priority)
-        .sum()
-}
+        .map(|l| alphabet.priority(scan_common_item(l).unwrap()).unwrap())
+        .sum();
+    let scan_badges = lines.chunks(3).filter_map(scan_badge).count();
+    let scan_time = start.elapsed();
 
-fn part_two(rucksacks: &[Rucksack]) -> usize {
-    rucksacks
-        .chunks(3)
-        .map(|group| priority(common_item(group).unwrap()))
-        .sum()
-}
+    let start = Instant::now();
+    let rucksacks = parse(&input, &alphabet).unwrap();
+    let mask_one = part_one(&rucksacks);
+    let mask_badges = rucksacks.chunks(3).filter_map(common_item).count();
+    let mask_time = start.elapsed();
 
-fn parse(input: &str) -> Vec<Rucksack> {
-    input.lines().map(Rucksack::from_input).collect()
+    println!("{} generated rucksacks", count);
+    println!(
+        "char scan: {:?} (part 1 sum {}, {} badges)",
+        scan_time, scan_one, scan_badges
+    );
+    println!(
+        "bitmask:   {:?} (part 1 sum {}, {} badges)",
+        mask_time, mask_one, mask_badges
+    );
+    println!(
+        "speedup: {:.1}x",
+        scan_time.as_secs_f64() / mask_time.as_secs_f64()
+    );
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|a| a == "--bench") {
+        let count = match args.get(i + 1) {
+            Some(count) => count.parse()?,
+            None => 300_000,
+        };
+        bench(count);
+        return Ok(());
+    }
+
+    let alphabet = match args.iter().position(|a| a == "--alphabet") {
+        Some(i) => ItemAlphabet::from_arg(args.get(i + 1).ok_or("--alphabet needs a value")?)?,
+        None => ItemAlphabet::AdventOfCode,
+    };
+
     let input = include_str!("../input/day03.txt");
-    let rucksacks = parse(input);
+    let rucksacks = parse(input, &alphabet)?;
     let rucksacks = rucksacks.as_slice();
 
     let sum = part_one(rucksacks);
     println!("Sum of common items priority (part 1): {}", sum);
 
-    let sum = part_two(rucksacks);
+    let sum = part_two(rucksacks)?;
     println!("Sum of badges (part 2): {}", sum);
 
+    if args.iter().any(|a| a == "--duplicates") {
+        print_duplicate_reports(input, &alphabet)?;
+    }
+
+    let group_size = match args.iter().position(|a| a == "--group-size") {
+        Some(i) => Some(
+            args.get(i + 1)
+                .ok_or("--group-size needs a value")?
+                .parse::<usize>()?,
+        ),
+        None => None,
+    };
+
+    if let Some(i) = args.iter().position(|a| a == "--analytics") {
+        let top = match args.get(i + 1).filter(|a| !a.starts_with("--")) {
+            Some(top) => top.parse()?,
+            None => 10,
+        };
+        Inventory::from_rucksacks(rucksacks, group_size.unwrap_or(3))?.print_tables(&alphabet, top);
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--analytics-csv") {
+        let path = args.get(i + 1).ok_or("--analytics-csv needs a path")?;
+        let inventory = Inventory::from_rucksacks(rucksacks, group_size.unwrap_or(3))?;
+        std::fs::write(path, inventory.to_csv(&alphabet))?;
+        println!("Wrote inventory analytics to {}", path);
+    }
+
+    if let Some(group_size) = group_size {
+        match badge_sum(rucksacks, group_size) {
+            Ok(sum) => println!("Sum of badges in groups of {}: {}", group_size, sum),
+            Err(e) => println!("No badge sum for groups of {}: {}", group_size, e),
+        }
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--find-groups") {
+        let group_size: usize = args
+            .get(i + 1)
+            .ok_or("--find-groups needs a value")?
+            .parse()?;
+        match find_groups(rucksacks, group_size) {
+            Some(groups) => {
+                for group in groups {
+                    let members: Vec<Rucksack> =
+                        group.iter().map(|r| rucksacks[*r].clone()).collect();
+                    let badge = common_item(&members)
+                        .and_then(|p| alphabet.item(p))
+                        .unwrap();
+                    println!("{:?} share {}", group, badge);
+                }
+            }
+            None => println!(
+                "No grouping of {} rucksacks into groups of {} sharing exactly one item",
+                rucksacks.len(),
+                group_size
+            ),
+        }
+    }
+
     Ok(())
 }
 
@@ -95,44 +856,61 @@ fn main() -> Result<(), Box<dyn Error>> {
 mod tests {
     use super::*;
 
+    const AOC: ItemAlphabet = ItemAlphabet::AdventOfCode;
+
+    fn rucksack(left: &str, right: &str) -> Rucksack {
+        Rucksack(
+            item_set(left, &AOC, 0).unwrap(),
+            item_set(right, &AOC, 0).unwrap(),
+        )
+    }
+
     fn test_rucksacks() -> Vec<Rucksack> {
         vec![
-            Rucksack(
-                "vJrwpWtwJgWr".chars().collect(),
-                "hcsFMMfFFhFp".chars().collect(),
-            ),
-            Rucksack(
-                "jqHRNqRjqzjGDLGL".chars().collect(),
-                "rsFMfFZSrLrFZsSL".chars().collect(),
-            ),
-            Rucksack("PmmdzqPrV".chars().collect(), "vPwwTWBwg".chars().collect()),
-            Rucksack(
-                "wMqvLMZHhHMvwLH".chars().collect(),
-                "jbvcjnnSBnvTQFn".chars().collect(),
-            ),
-            Rucksack("ttgJtRGJ".chars().collect(), "QctTZtZT".chars().collect()),
-            Rucksack(
-                "CrZsJsPPZsGz".chars().collect(),
-                "wwsLwLmpwMDw".chars().collect(),
-            ),
+            rucksack("vJrwpWtwJgWr", "hcsFMMfFFhFp"),
+            rucksack("jqHRNqRjqzjGDLGL", "rsFMfFZSrLrFZsSL"),
+            rucksack("PmmdzqPrV", "vPwwTWBwg"),
+            rucksack("wMqvLMZHhHMvwLH", "jbvcjnnSBnvTQFn"),
+            rucksack("ttgJtRGJ", "QctTZtZT"),
+            rucksack("CrZsJsPPZsGz", "wwsLwLmpwMDw"),
         ]
     }
 
     #[test]
     fn parse_test() {
         let input = include_str!("../input/day03_test.txt");
-        let result = parse(input);
+        let result = parse(input, &AOC).unwrap();
         let expected = test_rucksacks();
 
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn item_set_test() {
+        assert_eq!(item_set("aab", &AOC, 0).unwrap().low, 0b11);
+        assert_eq!(item_set("Z", &AOC, 0).unwrap().low, 1 << 51);
+        assert_eq!(item_set("Lz", &AOC, 0).unwrap().first(), Some(26));
+        assert_eq!(ItemSet::default().first(), None);
+
+        let mut set = ItemSet::default();
+        set.insert(200);
+        let mut a = ItemSet::default();
+        a.insert(1);
+        assert_eq!(set.high.len(), 3);
+        assert_eq!(set.first(), Some(200));
+        assert_eq!(set.union(&a).len(), 2);
+        assert_eq!(set.union(&a).first(), Some(1));
+        assert!(set.intersection(&a).is_empty());
+        assert_eq!(set.intersection(&a), ItemSet::default());
+        assert_eq!(set.union(&a).intersection(&a), a);
+    }
+
     #[test]
     fn common_item_test() {
         let expected: Vec<char> = "pLPvts".chars().collect();
         let result: Vec<char> = test_rucksacks()
             .iter()
-            .map(|r| r.common_item().unwrap())
+            .map(|r| AOC.item(r.common_item().unwrap()).unwrap())
             .collect();
 
         assert_eq!(result, expected);
@@ -141,12 +919,13 @@ mod tests {
     #[test]
     fn priority_test() {
         //16 (p), 38 (L), 42 (P), 22 (v), 20 (t), and 19 (s)
-        assert_eq!(priority('p'), 16);
-        assert_eq!(priority('L'), 38);
-        assert_eq!(priority('P'), 42);
-        assert_eq!(priority('v'), 22);
-        assert_eq!(priority('t'), 20);
-        assert_eq!(priority('s'), 19);
+        assert_eq!(AOC.priority('p'), Some(16));
+        assert_eq!(AOC.priority('L'), Some(38));
+        assert_eq!(AOC.priority('P'), Some(42));
+        assert_eq!(AOC.priority('v'), Some(22));
+        assert_eq!(AOC.priority('t'), Some(20));
+        assert_eq!(AOC.priority('s'), Some(19));
+        assert!((1..=52).all(|p| AOC.priority(AOC.item(p).unwrap()) == Some(p)));
     }
 
     #[test]
@@ -159,7 +938,222 @@ mod tests {
     #[test]
     fn part_two_test() {
         let rucksacks = test_rucksacks();
-        let result = part_two(rucksacks.as_slice());
+        let result = part_two(rucksacks.as_slice()).unwrap();
         assert_eq!(result, 70);
     }
+
+    #[test]
+    fn bitmask_matches_char_scan() {
+        let input = generate_input(300, 7);
+        let expected: Vec<Option<char>> = input.lines().map(scan_common_item).collect();
+        let result: Vec<Option<char>> = parse(&input, &AOC)
+            .unwrap()
+            .iter()
+            .map(|r| r.common_item().and_then(|p| AOC.item(p)))
+            .collect();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn badge_sum_test() {
+        let rucksacks = test_rucksacks();
+
+        assert_eq!(badge_sum(rucksacks.as_slice(), 3).unwrap(), 70);
+        assert_eq!(badge_sum(&rucksacks[..3], 3).unwrap(), 18);
+        assert_eq!(
+            badge_sum(parse("abab\nacac\nxyxy\nxzxz", &AOC).unwrap().as_slice(), 2).unwrap(),
+            25
+        );
+
+        // the second group has nothing in common
+        let rucksacks = parse("abab\nacac\nxyxy\nzwzw", &AOC).unwrap();
+        let error = badge_sum(rucksacks.as_slice(), 2).unwrap_err();
+        assert_eq!(error.to_string(), "1 groups of 2 share no item: 2");
+        assert!(badge_sum(rucksacks.as_slice(), 0).is_err());
+
+        let rucksacks = parse("abab\nacac\nxyxy", &AOC).unwrap();
+        assert_eq!(
+            badge_sum(rucksacks.as_slice(), 2).unwrap_err().to_string(),
+            "3 rucksacks can't be split into groups of 2"
+        );
+    }
+
+    #[test]
+    fn find_groups_test() {
+        let rucksacks = test_rucksacks();
+        let groups = find_groups(rucksacks.as_slice(), 3).unwrap();
+
+        assert_eq!(groups, vec![vec![0, 1, 2], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    fn find_groups_out_of_order() {
+        let rucksacks = parse("abcabc\nadeade\nxyzxyz\nbfgbfg\nxuvxuv\ncgwcgw", &AOC).unwrap();
+        let groups = find_groups(rucksacks.as_slice(), 2).unwrap();
+
+        assert_eq!(groups, vec![vec![0, 1], vec![2, 4], vec![3, 5]]);
+    }
+
+    #[test]
+    fn find_groups_proves_none_exist() {
+        let rucksacks = parse("abab\nabab\ncdcd\nefef", &AOC).unwrap();
+
+        assert_eq!(find_groups(rucksacks.as_slice(), 2), None);
+        assert_eq!(find_groups(rucksacks.as_slice(), 3), None);
+    }
+
+    #[test]
+    fn duplicate_report_test() {
+        let report = DuplicateReport::from_input("vJrwpWtwJgWrhcsFMMfFFhFp", &AOC).unwrap();
+
+        assert_eq!(
+            report.duplicates,
+            vec![Duplicate {
+                item: 'p',
+                left: 1,
+                right: 1,
+            }]
+        );
+        assert!(!report.is_flagged());
+        assert_eq!(report.repair_moves(), 1);
+    }
+
+    #[test]
+    fn duplicate_report_flags_zero_or_many() {
+        let none = DuplicateReport::from_input("abcdef", &AOC).unwrap();
+        let many = DuplicateReport::from_input("aaabZbbaZb", &AOC).unwrap();
+
+        assert!(none.is_flagged());
+        assert_eq!(none.repair_moves(), 0);
+        assert!(many.is_flagged());
+        assert_eq!(many.duplicates[0].repair(), (1, Compartment::Left));
+        assert_eq!(many.duplicates[1].repair(), (1, Compartment::Right));
+        assert_eq!(many.repair_moves(), 3);
+    }
+
+    #[test]
+    fn part_one_without_common_items() {
+        let rucksacks = parse("abcd\nvJrwpWtwJgWrhcsFMMfFFhFp", &AOC).unwrap();
+
+        assert_eq!(part_one(rucksacks.as_slice()), 16);
+    }
+
+    #[test]
+    fn alphabet_from_arg() {
+        assert_eq!(ItemAlphabet::from_arg("aoc").unwrap(), AOC);
+        assert_eq!(
+            ItemAlphabet::from_arg("table:xyz").unwrap(),
+            ItemAlphabet::Table(vec!['x', 'y', 'z'])
+        );
+        assert_eq!(
+            ItemAlphabet::from_arg("unicode:numeric").unwrap(),
+            ItemAlphabet::Unicode(UnicodeItems::new(UnicodeCategory::Numeric))
+        );
+        assert!(ItemAlphabet::from_arg("table:xx").is_err());
+        assert!(ItemAlphabet::from_arg("unicode:emoji").is_err());
+    }
+
+    #[test]
+    fn rucksack_rejects_items_outside_alphabet() {
+        assert_eq!(
+            Rucksack::from_input("ab1b", &AOC),
+            Err(ItemError::Invalid {
+                item: '1',
+                position: 3
+            })
+        );
+        assert_eq!(
+            Rucksack::from_input("abc", &AOC),
+            Err(ItemError::OddCount(3))
+        );
+        assert!(parse("abab\nab-b", &AOC)
+            .unwrap_err()
+            .to_string()
+            .starts_with("line 2:"));
+    }
+
+    #[test]
+    fn custom_alphabets() {
+        let digits = ItemAlphabet::from_arg("table:0123456789").unwrap();
+        let rucksack = Rucksack::from_input("123941", &digits).unwrap();
+        assert_eq!(rucksack.common_item(), Some(2));
+        assert_eq!(digits.item(2), Some('1'));
+
+        // compartments are split by chars, so multi-byte items stay whole
+        let greek = ItemAlphabet::from_arg("unicode:alphabetic").unwrap();
+        let rucksack = Rucksack::from_input("αβγδβω", &greek).unwrap();
+        let beta = greek.priority('β').unwrap();
+        assert_eq!(rucksack.common_item(), Some(beta));
+        assert_eq!(greek.item(beta), Some('β'));
+        assert_eq!(greek.priority('1'), None);
+
+        // priorities count only the category's chars, so far apart items stay close
+        assert_eq!(greek.priority('A'), Some(1));
+        assert_eq!(greek.priority('a'), Some(27));
+        let rare = greek.priority('𠀀').unwrap();
+        assert!(rare < 200_000);
+        assert_eq!(greek.item(rare), Some('𠀀'));
+    }
+
+    #[test]
+    fn item_set_priorities() {
+        let set = item_set("zaZ", &AOC, 0).unwrap();
+
+        assert_eq!(set.priorities().collect::<Vec<usize>>(), vec![1, 26, 52]);
+    }
+
+    #[test]
+    fn inventory_test() {
+        let rucksacks = parse("abab\nacca\nbcbc", &AOC).unwrap();
+        let inventory = Inventory::from_rucksacks(rucksacks.as_slice(), 3).unwrap();
+
+        assert_eq!(
+            inventory.frequency,
+            BTreeMap::from([(1, 2), (2, 2), (3, 2)])
+        );
+        assert_eq!(
+            inventory.duplicates,
+            BTreeMap::from([(1, 2), (2, 2), (3, 2)])
+        );
+        assert_eq!(inventory.badges, BTreeMap::new());
+        assert_eq!(
+            inventory.pairs,
+            BTreeMap::from([((1, 2), 1), ((1, 3), 1), ((2, 3), 1)])
+        );
+    }
+
+    #[test]
+    fn inventory_csv() {
+        let rucksacks = parse("abab\naxax", &AOC).unwrap();
+        let csv = Inventory::from_rucksacks(rucksacks.as_slice(), 2)
+            .unwrap()
+            .to_csv(&AOC);
+
+        assert_eq!(
+            csv,
+            "table,item,other,count\n\
+             frequency,a,,2\n\
+             frequency,b,,1\n\
+             frequency,x,,1\n\
+             duplicates,a,,2\n\
+             duplicates,b,,1\n\
+             duplicates,x,,1\n\
+             badges,a,,1\n\
+             pairs,a,b,1\n\
+             pairs,a,x,1\n"
+        );
+
+        let quoted = ItemAlphabet::from_arg("table:,\"a").unwrap();
+        let rucksacks = parse(",\",\"", &quoted).unwrap();
+        let csv = Inventory::from_rucksacks(rucksacks.as_slice(), 1)
+            .unwrap()
+            .to_csv(&quoted);
+        assert!(csv.contains("\nfrequency,\",\",,1\n"));
+        assert!(csv.contains("\npairs,\",\",\"\"\"\",1\n"));
+        assert!(Inventory::from_rucksacks(rucksacks.as_slice(), 0).is_err());
+
+        let rucksacks = parse("abab\nacac\nxyxy", &AOC).unwrap();
+        assert!(Inventory::from_rucksacks(rucksacks.as_slice(), 2).is_err());
+    }
 }
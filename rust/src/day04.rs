@@ -1,35 +1,201 @@
+use itertools::Itertools;
 use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
 
-type Range = (usize, usize);
-type WorkerPair = (Range, Range);
+// an inclusive range of section IDs
+#[derive(PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+struct SectionRange {
+    start: usize,
+    end: usize,
+}
+
+#[derive(PartialEq, Debug)]
+enum RangeError {
+    Malformed(String),
+    Reversed { start: usize, end: usize },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Malformed(s) => write!(f, "expected a range like 2-4, got {:?}", s),
+            RangeError::Reversed { start, end } => {
+                write!(f, "range {}-{} ends before it starts", start, end)
+            }
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+impl SectionRange {
+    fn new(start: usize, end: usize) -> Result<Self, RangeError> {
+        if start > end {
+            return Err(RangeError::Reversed { start, end });
+        }
+        Ok(SectionRange { start, end })
+    }
+
+    // saturates for 0-MAX, the one range with a section too many to count
+    fn len(&self) -> usize {
+        (self.end - self.start).saturating_add(1)
+    }
 
-fn parse(input: &str) -> Vec<WorkerPair> {
+    fn contains_section(&self, section: usize) -> bool {
+        self.start <= section && section <= self.end
+    }
+
+    fn contains(&self, other: &SectionRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn overlaps(&self, other: &SectionRange) -> bool {
+        self.contains_section(other.start) || other.contains_section(self.start)
+    }
+
+    fn intersection(&self, other: &SectionRange) -> Option<SectionRange> {
+        SectionRange::new(self.start.max(other.start), self.end.min(other.end)).ok()
+    }
+
+    // sections in either range, as one range when they overlap or touch and two otherwise
+    fn union(&self, other: &SectionRange) -> Vec<SectionRange> {
+        let (first, second) = if self <= other {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if first
+            .end
+            .checked_add(1)
+            .is_none_or(|next| second.start <= next)
+        {
+            vec![SectionRange {
+                start: first.start,
+                end: first.end.max(second.end),
+            }]
+        } else {
+            vec![*first, *second]
+        }
+    }
+
+    // sections in this range but not the other, split in two when the other is strictly inside
+    fn difference(&self, other: &SectionRange) -> Vec<SectionRange> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let mut remaining = vec![];
+        if self.start < other.start {
+            remaining.push(SectionRange {
+                start: self.start,
+                end: other.start - 1,
+            });
+        }
+        if other.end < self.end {
+            remaining.push(SectionRange {
+                start: other.end + 1,
+                end: self.end,
+            });
+        }
+        remaining
+    }
+}
+
+impl fmt::Display for SectionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for SectionRange {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| RangeError::Malformed(s.to_string()))?;
+        let start = start
+            .parse()
+            .map_err(|_| RangeError::Malformed(s.to_string()))?;
+        let end = end
+            .parse()
+            .map_err(|_| RangeError::Malformed(s.to_string()))?;
+        SectionRange::new(start, end)
+    }
+}
+
+type WorkerPair = (SectionRange, SectionRange);
+
+// every elf assigned on one line, in input order
+#[derive(PartialEq, Debug, Clone)]
+struct Crew(Vec<SectionRange>);
+
+impl Crew {
+    // pairs of elves (by position on the line) whose ranges overlap
+    fn overlap_graph(&self) -> Vec<(usize, usize)> {
+        (0..self.0.len())
+            .tuple_combinations()
+            .filter(|&(i, j)| self.0[i].overlaps(&self.0[j]))
+            .collect()
+    }
+
+    fn has_overlap(&self) -> bool {
+        let mut sorted = self.0.clone();
+        sorted.sort();
+        sorted.windows(2).any(|w| w[0].overlaps(&w[1]))
+    }
+
+    // the elf whose range contains every other range in the crew, if there is one
+    fn container(&self) -> Option<usize> {
+        let start = self.0.iter().map(|r| r.start).min()?;
+        let end = self.0.iter().map(|r| r.end).max()?;
+        self.0.iter().position(|r| r.start == start && r.end == end)
+    }
+}
+
+fn parse_crews(input: &str) -> Result<Vec<Crew>, Box<dyn Error>> {
     input
         .lines()
-        .map(|line| {
-            let pair: Vec<Range> = line
+        .enumerate()
+        .map(|(i, line)| {
+            let ranges = line
                 .split(',')
-                .map(|part| {
-                    let xs: Vec<usize> = part
-                        .split('-')
-                        .map(|s| s.to_string().parse::<usize>().unwrap())
-                        .collect();
-                    (xs[0], xs[1])
-                })
-                .collect();
-            (pair[0], pair[1])
+                .map(|r| r.parse())
+                .collect::<Result<_, RangeError>>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            Ok(Crew(ranges))
+        })
+        .collect()
+}
+
+fn parse(input: &str) -> Result<Vec<WorkerPair>, Box<dyn Error>> {
+    parse_crews(input)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, crew)| match crew.0[..] {
+            [a, b] => Ok((a, b)),
+            _ => Err(format!("line {}: expected two ranges, got {}", i + 1, crew.0.len()).into()),
         })
         .collect()
 }
 
+fn crews_with_overlap(crews: &[Crew]) -> usize {
+    crews.iter().filter(|c| c.has_overlap()).count()
+}
+
+fn crews_with_container(crews: &[Crew]) -> usize {
+    crews.iter().filter(|c| c.container().is_some()).count()
+}
+
 fn fully_contained(wp: WorkerPair) -> bool {
-    let ((a, b), (x, y)) = wp;
-    (a <= x && b >= y) || (x <= a && y >= b)
+    let (a, b) = wp;
+    a.contains(&b) || b.contains(&a)
 }
 
 fn overlap(wp: WorkerPair) -> bool {
-    let ((a, b), (x, y)) = wp;
-    (a <= y && a >= x) || (b <= y && b >= x) || (x <= b && x >= a) || (y <= b && y >= a)
+    let (a, b) = wp;
+    a.overlaps(&b)
 }
 
 fn contained_count(worker_pairs: &[WorkerPair]) -> usize {
@@ -51,10 +217,352 @@ fn overlap_count(worker_pairs: &[WorkerPair]) -> usize {
         .fold(0, |acc, wp| if overlap(*wp) { acc + 1 } else { acc })
 }
 
+// maximal runs of sections covered by the same number of elves, from the lowest assigned
+// section to the highest, including runs nobody covers
+#[derive(PartialEq, Debug)]
+struct Coverage {
+    segments: Vec<(SectionRange, usize)>,
+}
+
+impl Coverage {
+    fn from_ranges(ranges: impl Iterator<Item = SectionRange>) -> Self {
+        // +1 just before a range starts and -1 just after it ends. the boundary is kept as
+        // (section, after) rather than end + 1 so a range ending at usize::MAX can't overflow
+        let mut events: Vec<((usize, bool), isize)> = ranges
+            .flat_map(|r| [((r.start, false), 1), ((r.end, true), -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut segments: Vec<(SectionRange, usize)> = vec![];
+        let mut count = 0;
+        let mut i = 0;
+        while i < events.len() {
+            let boundary = events[i].0;
+            while i < events.len() && events[i].0 == boundary {
+                count += events[i].1;
+                i += 1;
+            }
+            let Some(&((next, next_after), _)) = events.get(i) else {
+                break;
+            };
+            let start = if boundary.1 {
+                boundary.0 + 1
+            } else {
+                boundary.0
+            };
+            let end = if next_after { next } else { next - 1 };
+            // a range ending right where the next one starts leaves nothing in between
+            if let Ok(range) = SectionRange::new(start, end) {
+                match segments.last_mut() {
+                    Some((last, last_count)) if *last_count == count as usize => {
+                        last.end = range.end
+                    }
+                    _ => segments.push((range, count as usize)),
+                }
+            }
+        }
+        Coverage { segments }
+    }
+
+    fn at(&self, section: usize) -> usize {
+        let i = self.segments.partition_point(|(r, _)| r.end < section);
+        match self.segments.get(i) {
+            Some((r, count)) if r.contains_section(section) => *count,
+            _ => 0,
+        }
+    }
+
+    fn max_congestion(&self) -> (usize, Vec<SectionRange>) {
+        let max = self.segments.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        let ranges = self
+            .segments
+            .iter()
+            .filter(|(_, c)| *c == max)
+            .map(|(r, _)| *r)
+            .collect();
+        (max, ranges)
+    }
+
+    fn gaps(&self) -> Vec<SectionRange> {
+        self.segments
+            .iter()
+            .filter(|(_, c)| *c == 0)
+            .map(|(r, _)| *r)
+            .collect()
+    }
+
+    // sections covered by more than one elf
+    fn overlapped_sections(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|(_, c)| *c > 1)
+            .map(|(r, _)| r.len())
+            .sum()
+    }
+}
+
+fn all_ranges(worker_pairs: &[WorkerPair]) -> impl Iterator<Item = SectionRange> + '_ {
+    worker_pairs.iter().flat_map(|(a, b)| [*a, *b])
+}
+
+// one elf's range, with the index of its pair in the input and its position in the pair
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Assignment {
+    pair: usize,
+    elf: usize,
+    range: SectionRange,
+}
+
+fn assignments(worker_pairs: &[WorkerPair]) -> Vec<Assignment> {
+    worker_pairs
+        .iter()
+        .enumerate()
+        .flat_map(|(pair, (a, b))| {
+            [(0, *a), (1, *b)].map(|(elf, range)| Assignment { pair, elf, range })
+        })
+        .collect()
+}
+
+// assignments sorted by start, read as an implicit balanced tree where the node for entries
+// lo..hi is the middle entry and `min_end` and `max_end` hold the nearest and furthest end in
+// that node's subtree
+struct IntervalTree {
+    entries: Vec<Assignment>,
+    min_end: Vec<usize>,
+    max_end: Vec<usize>,
+}
+
+impl IntervalTree {
+    fn new(mut entries: Vec<Assignment>) -> Self {
+        entries.sort_by_key(|a| a.range);
+        let mut tree = IntervalTree {
+            min_end: vec![0; entries.len()],
+            max_end: vec![0; entries.len()],
+            entries,
+        };
+        tree.build(0, tree.entries.len());
+        tree
+    }
+
+    // the (min, max) end in entries lo..hi
+    fn build(&mut self, lo: usize, hi: usize) -> (usize, usize) {
+        if lo >= hi {
+            return (usize::MAX, 0);
+        }
+        let mid = (lo + hi) / 2;
+        let left = self.build(lo, mid);
+        let right = self.build(mid + 1, hi);
+        let end = self.entries[mid].range.end;
+        self.min_end[mid] = end.min(left.0).min(right.0);
+        self.max_end[mid] = end.max(left.1).max(right.1);
+        (self.min_end[mid], self.max_end[mid])
+    }
+
+    // every entry starting at or before `start_max` and ending at or after `end_min`
+    fn dominating(&self, start_max: usize, end_min: usize) -> Vec<Assignment> {
+        let mut found = vec![];
+        self.collect(0, self.entries.len(), start_max, end_min, &mut found);
+        found
+    }
+
+    fn collect(
+        &self,
+        lo: usize,
+        hi: usize,
+        start_max: usize,
+        end_min: usize,
+        found: &mut Vec<Assignment>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.max_end[mid] < end_min {
+            return;
+        }
+        self.collect(lo, mid, start_max, end_min, found);
+        let entry = self.entries[mid];
+        if entry.range.start > start_max {
+            // everything to the right starts later still
+            return;
+        }
+        if entry.range.end >= end_min {
+            found.push(entry);
+        }
+        self.collect(mid + 1, hi, start_max, end_min, found);
+    }
+
+    fn stabbing(&self, section: usize) -> Vec<Assignment> {
+        self.dominating(section, section)
+    }
+
+    fn overlapping(&self, query: &SectionRange) -> Vec<Assignment> {
+        self.dominating(query.end, query.start)
+    }
+
+    fn containing(&self, query: &SectionRange) -> Vec<Assignment> {
+        self.dominating(query.start, query.end)
+    }
+
+    // the entries starting inside the query are a slice of the sorted entries, and `min_end`
+    // skips any subtree where all of them end past the query, so only the two paths to the slice
+    // edges and the paths to each match are walked
+    fn contained_in(&self, query: &SectionRange) -> Vec<Assignment> {
+        let first = self
+            .entries
+            .partition_point(|a| a.range.start < query.start);
+        let last = self.entries.partition_point(|a| a.range.start <= query.end);
+        let mut found = vec![];
+        self.collect_within(0, self.entries.len(), first..last, query.end, &mut found);
+        found
+    }
+
+    fn collect_within(
+        &self,
+        lo: usize,
+        hi: usize,
+        starting: Range<usize>,
+        end_max: usize,
+        found: &mut Vec<Assignment>,
+    ) {
+        if lo >= hi || hi <= starting.start || lo >= starting.end {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.min_end[mid] > end_max {
+            return;
+        }
+        self.collect_within(lo, mid, starting.clone(), end_max, found);
+        let entry = self.entries[mid];
+        if starting.contains(&mid) && entry.range.end <= end_max {
+            found.push(entry);
+        }
+        self.collect_within(mid + 1, hi, starting, end_max, found);
+    }
+}
+
+fn run_query(
+    tree: &IntervalTree,
+    kind: &str,
+    arg: &str,
+) -> Result<Vec<Assignment>, Box<dyn Error>> {
+    let found = match kind {
+        "stab" => tree.stabbing(arg.parse()?),
+        "overlap" => tree.overlapping(&arg.parse()?),
+        "containing" => tree.containing(&arg.parse()?),
+        "within" => tree.contained_in(&arg.parse()?),
+        _ => {
+            return Err(format!(
+                "unknown query {}, expected stab, overlap, containing or within",
+                kind
+            )
+            .into())
+        }
+    };
+    Ok(found)
+}
+
+// (sections both elves cover, sections only one elf covers, sections either covers)
+fn section_counts(wp: WorkerPair) -> (usize, usize, usize) {
+    let (a, b) = wp;
+    let len = |ranges: Vec<SectionRange>| ranges.iter().map(|r| r.len()).sum::<usize>();
+    let shared = a.intersection(&b).map_or(0, |r| r.len());
+    let exclusive = len(a.difference(&b)) + len(b.difference(&a));
+    (shared, exclusive, len(a.union(&b)))
+}
+
+// sections an elf gains or loses when moved from one range to another
+fn change_cost(before: &SectionRange, after: &SectionRange) -> usize {
+    before.start.abs_diff(after.start) + before.end.abs_diff(after.end)
+}
+
+// the cheapest way to cut an overlapping pair's union into two disjoint ranges, with either elf
+// taking the lower half. None when the ranges are already apart or the union is a single section.
+// each elf's cost is linear in the split point between its own endpoints, so only splits just
+// before a start or at an end need trying
+fn split_pair(wp: WorkerPair) -> Option<(WorkerPair, usize)> {
+    let (a, b) = wp;
+    let union = match a.union(&b).as_slice() {
+        [union] if union.start < union.end => *union,
+        _ => return None,
+    };
+    [
+        a.start.saturating_sub(1),
+        a.end,
+        b.start.saturating_sub(1),
+        b.end,
+    ]
+    .into_iter()
+    .map(|k| k.clamp(union.start, union.end - 1))
+    .unique()
+    .flat_map(|k| {
+        let lower = SectionRange {
+            start: union.start,
+            end: k,
+        };
+        let upper = SectionRange {
+            start: k + 1,
+            end: union.end,
+        };
+        [(lower, upper), (upper, lower)]
+    })
+    .map(|(a2, b2)| ((a2, b2), change_cost(&a, &a2) + change_cost(&b, &b2)))
+    .min_by_key(|(_, cost)| *cost)
+}
+
+#[derive(PartialEq, Debug)]
+struct Reassignment {
+    pair: usize,
+    before: WorkerPair,
+    after: WorkerPair,
+    cost: usize,
+}
+
+#[derive(Debug, Default)]
+struct ReassignmentPlan {
+    moves: Vec<Reassignment>,
+    // overlapping pairs that share one section and cannot both keep a range
+    unsplittable: Vec<usize>,
+}
+
+impl ReassignmentPlan {
+    fn new(worker_pairs: &[WorkerPair]) -> Self {
+        let mut plan = ReassignmentPlan::default();
+        for (pair, wp) in worker_pairs.iter().enumerate() {
+            if !overlap(*wp) {
+                continue;
+            }
+            match split_pair(*wp) {
+                Some((after, cost)) => plan.moves.push(Reassignment {
+                    pair,
+                    before: *wp,
+                    after,
+                    cost,
+                }),
+                None => plan.unsplittable.push(pair),
+            }
+        }
+        plan
+    }
+
+    fn cost(&self) -> usize {
+        self.moves.iter().map(|m| m.cost).sum()
+    }
+
+    fn apply(&self, worker_pairs: &[WorkerPair]) -> Vec<WorkerPair> {
+        let mut pairs = worker_pairs.to_vec();
+        for m in &self.moves {
+            pairs[m.pair] = m.after;
+        }
+        pairs
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
     let input = include_str!("../input/day04.txt");
 
-    let elf_pairs = parse(input);
+    let elf_pairs = parse(input)?;
     let elf_pairs = elf_pairs.as_slice();
 
     let part_one = contained_count(elf_pairs);
@@ -63,6 +571,110 @@ fn main() -> Result<(), Box<dyn Error>> {
     let part_two = overlap_count(elf_pairs);
     println!("Overlap Count (part 2): {:?}", part_two);
 
+    if args.iter().any(|a| a == "--sections") {
+        let (shared, exclusive, covered) = elf_pairs.iter().fold((0, 0, 0), |acc, wp| {
+            let (s, e, c) = section_counts(*wp);
+            (acc.0 + s, acc.1 + e, acc.2 + c)
+        });
+        println!(
+            "Sections covered by both elves: {}, by one elf: {}, by either: {}",
+            shared, exclusive, covered
+        );
+    }
+
+    let coverage = Coverage::from_ranges(all_ranges(elf_pairs));
+    if args.iter().any(|a| a == "--coverage") {
+        let (max, congested) = coverage.max_congestion();
+        println!(
+            "Most elves on one section: {} in {}",
+            max,
+            congested.iter().join(", ")
+        );
+        let gaps = coverage.gaps();
+        if gaps.is_empty() {
+            println!("Uncovered gaps: none");
+        } else {
+            println!("Uncovered gaps: {}", gaps.iter().join(", "));
+        }
+        println!(
+            "Sections covered by more than one elf: {}",
+            coverage.overlapped_sections()
+        );
+        for (range, count) in &coverage.segments {
+            println!("{:>3}-{:<3} {}", range.start, range.end, count);
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--query") {
+        let kind = args
+            .get(i + 1)
+            .ok_or("--query needs a kind and a section or range")?;
+        let arg = args
+            .get(i + 2)
+            .ok_or("--query needs a kind and a section or range")?;
+        let tree = IntervalTree::new(assignments(elf_pairs));
+        let found = run_query(&tree, kind, arg)?;
+        println!("{} assignments match {} {}", found.len(), kind, arg);
+        for a in found {
+            println!("  line {} elf {}: {}", a.pair + 1, a.elf + 1, a.range);
+        }
+    }
+
+    if args.iter().any(|a| a == "--reassign") {
+        let plan = ReassignmentPlan::new(elf_pairs);
+        for m in &plan.moves {
+            println!(
+                "line {}: {},{} -> {},{} (cost {})",
+                m.pair + 1,
+                m.before.0,
+                m.before.1,
+                m.after.0,
+                m.after.1,
+                m.cost
+            );
+        }
+        for pair in &plan.unsplittable {
+            println!("line {}: both elves share a single section", pair + 1);
+        }
+        println!(
+            "Reassigned {} pairs at a cost of {} sections, {} left overlapping",
+            plan.moves.len(),
+            plan.cost(),
+            plan.unsplittable.len()
+        );
+        println!(
+            "Overlap count after reassignment: {}",
+            overlap_count(&plan.apply(elf_pairs))
+        );
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--crews") {
+        let crews = match args.get(i + 1).filter(|a| !a.starts_with("--")) {
+            Some(path) => parse_crews(&std::fs::read_to_string(path)?)?,
+            None => parse_crews(input)?,
+        };
+        println!(
+            "Crews with an overlap: {}, crews where one range contains the rest: {}",
+            crews_with_overlap(&crews),
+            crews_with_container(&crews)
+        );
+        for (line, crew) in crews.iter().enumerate() {
+            let graph = crew.overlap_graph();
+            if !graph.is_empty() {
+                let edges = graph.iter().map(|(a, b)| format!("{}-{}", a + 1, b + 1));
+                println!("  line {}: {}", line + 1, edges.format(" "));
+            }
+        }
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--section") {
+        let section: usize = args.get(i + 1).ok_or("--section needs a value")?.parse()?;
+        println!(
+            "Elves covering section {}: {}",
+            section,
+            coverage.at(section)
+        );
+    }
+
     Ok(())
 }
 
@@ -70,26 +682,43 @@ fn main() -> Result<(), Box<dyn Error>> {
 mod tests {
     use super::*;
 
+    fn range(start: usize, end: usize) -> SectionRange {
+        SectionRange::new(start, end).unwrap()
+    }
+
     fn worker_pairs() -> Vec<WorkerPair> {
         vec![
-            ((2, 4), (6, 8)),
-            ((2, 3), (4, 5)),
-            ((5, 7), (7, 9)),
-            ((2, 8), (3, 7)),
-            ((6, 6), (4, 6)),
-            ((2, 6), (4, 8)),
+            (range(2, 4), range(6, 8)),
+            (range(2, 3), range(4, 5)),
+            (range(5, 7), range(7, 9)),
+            (range(2, 8), range(3, 7)),
+            (range(6, 6), range(4, 6)),
+            (range(2, 6), range(4, 8)),
         ]
     }
 
     #[test]
     fn parse_test() {
         let input = include_str!("../input/day04_test.txt");
-        let result = parse(input);
+        let result = parse(input).unwrap();
         let expected = worker_pairs();
 
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "4-2".parse::<SectionRange>(),
+            Err(RangeError::Reversed { start: 4, end: 2 })
+        );
+        assert_eq!(
+            "4".parse::<SectionRange>(),
+            Err(RangeError::Malformed("4".to_string()))
+        );
+        assert!(parse("2-4;6-8").is_err());
+    }
+
     #[test]
     fn contained_count_test() {
         let wp = worker_pairs();
@@ -105,4 +734,200 @@ mod tests {
 
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn section_counts_test() {
+        assert_eq!(section_counts((range(2, 6), range(4, 8))), (3, 4, 7));
+        assert_eq!(section_counts((range(2, 4), range(6, 8))), (0, 6, 6));
+        assert_eq!(section_counts((range(2, 8), range(3, 7))), (5, 2, 7));
+    }
+
+    #[test]
+    fn crews() {
+        let crews = parse_crews("2-4,6-8,3-3\n1-9,2-3,4-5,9-9\n5-7\n1-2,5-6,3-4").unwrap();
+
+        assert_eq!(crews[0].overlap_graph(), vec![(0, 2)]);
+        assert_eq!(crews[1].overlap_graph(), vec![(0, 1), (0, 2), (0, 3)]);
+        assert_eq!(crews[3].overlap_graph(), vec![]);
+        assert_eq!(crews_with_overlap(&crews), 2);
+        assert_eq!(crews[1].container(), Some(0));
+        assert_eq!(crews[2].container(), Some(0));
+        assert_eq!(crews_with_container(&crews), 2);
+
+        // pairs stay consistent with the two-elf metrics
+        let input = include_str!("../input/day04.txt");
+        let crews = parse_crews(input).unwrap();
+        let pairs = parse(input).unwrap();
+        assert_eq!(crews_with_overlap(&crews), overlap_count(&pairs));
+        assert_eq!(crews_with_container(&crews), contained_count(&pairs));
+
+        assert!(parse("2-4,6-8,3-3").is_err());
+        assert!(parse_crews("2-4,x").is_err());
+    }
+
+    #[test]
+    fn reassignment_plan() {
+        let wp = worker_pairs();
+        let plan = ReassignmentPlan::new(&wp);
+
+        assert_eq!(
+            plan.moves
+                .iter()
+                .map(|m| (m.pair, m.cost))
+                .collect::<Vec<_>>(),
+            vec![(2, 1), (3, 7), (4, 1), (5, 3)]
+        );
+        assert_eq!(plan.moves[2].after, (range(6, 6), range(4, 5)));
+        assert_eq!(plan.cost(), 12);
+
+        let fixed = plan.apply(&wp);
+        assert_eq!(overlap_count(&fixed), 0);
+        for (before, after) in wp.iter().zip(&fixed) {
+            assert_eq!(
+                before
+                    .0
+                    .union(&before.1)
+                    .iter()
+                    .map(|r| r.len())
+                    .sum::<usize>(),
+                after
+                    .0
+                    .union(&after.1)
+                    .iter()
+                    .map(|r| r.len())
+                    .sum::<usize>()
+            );
+        }
+
+        let stuck = ReassignmentPlan::new(&[(range(3, 3), range(3, 3))]);
+        assert!(stuck.moves.is_empty());
+        assert_eq!(stuck.unsplittable, vec![0]);
+        assert_eq!(split_pair((range(3, 3), range(3, 3))), None);
+        assert_eq!(split_pair((range(2, 3), range(5, 6))), None);
+        assert_eq!(
+            split_pair((range(3, 3), range(3, 4))),
+            Some(((range(3, 3), range(4, 4)), 1))
+        );
+        assert_eq!(
+            split_pair((range(1, 300_000_000), range(2, 300_000_000))),
+            Some(((range(1, 1), range(2, 300_000_000)), 299_999_999))
+        );
+    }
+
+    #[test]
+    fn coverage_test() {
+        let wp = worker_pairs();
+        let coverage = Coverage::from_ranges(all_ranges(&wp));
+
+        assert_eq!(coverage.segments[0], (range(2, 2), 4));
+        assert_eq!(coverage.at(1), 0);
+        assert_eq!(coverage.at(6), 8);
+        assert_eq!(coverage.at(9), 1);
+        assert_eq!(coverage.at(10), 0);
+        assert_eq!(coverage.max_congestion(), (8, vec![range(6, 6)]));
+        assert_eq!(coverage.overlapped_sections(), 7);
+    }
+
+    #[test]
+    fn coverage_gaps() {
+        let ranges = [range(1, 3), range(7, 8), range(2, 3), range(10, 10)];
+        let coverage = Coverage::from_ranges(ranges.into_iter());
+
+        assert_eq!(
+            coverage.segments,
+            vec![
+                (range(1, 1), 1),
+                (range(2, 3), 2),
+                (range(4, 6), 0),
+                (range(7, 8), 1),
+                (range(9, 9), 0),
+                (range(10, 10), 1),
+            ]
+        );
+        assert_eq!(coverage.gaps(), vec![range(4, 6), range(9, 9)]);
+        assert_eq!(coverage.overlapped_sections(), 2);
+
+        let ranges = [range(1, 3), range(4, usize::MAX)];
+        let coverage = Coverage::from_ranges(ranges.into_iter());
+        assert_eq!(coverage.segments, vec![(range(1, usize::MAX), 1)]);
+    }
+
+    fn matches(found: Vec<Assignment>) -> Vec<(usize, usize)> {
+        let mut found: Vec<(usize, usize)> = found.iter().map(|a| (a.pair, a.elf)).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn interval_tree_queries() {
+        let wp = worker_pairs();
+        let tree = IntervalTree::new(assignments(&wp));
+
+        assert_eq!(matches(tree.stabbing(9)), vec![(2, 1)]);
+        assert_eq!(matches(tree.stabbing(1)), vec![]);
+        assert_eq!(
+            matches(tree.overlapping(&range(8, 20))),
+            vec![(0, 1), (2, 1), (3, 0), (5, 1)]
+        );
+        assert_eq!(matches(tree.containing(&range(3, 7))), vec![(3, 0), (3, 1)]);
+        assert_eq!(
+            matches(tree.contained_in(&range(4, 6))),
+            vec![(1, 1), (4, 0), (4, 1)]
+        );
+    }
+
+    #[test]
+    fn interval_tree_matches_linear_scan() {
+        let wp = parse(include_str!("../input/day04.txt")).unwrap();
+        let all = assignments(&wp);
+        let tree = IntervalTree::new(all.clone());
+
+        for (start, end) in [(1, 1), (40, 55), (13, 13), (90, 99), (30, 31)] {
+            let query = range(start, end);
+            let expected = |f: &dyn Fn(&SectionRange) -> bool| {
+                matches(all.iter().filter(|a| f(&a.range)).copied().collect())
+            };
+            assert_eq!(
+                matches(tree.overlapping(&query)),
+                expected(&|r| r.overlaps(&query))
+            );
+            assert_eq!(
+                matches(tree.containing(&query)),
+                expected(&|r| r.contains(&query))
+            );
+            assert_eq!(
+                matches(tree.contained_in(&query)),
+                expected(&|r| query.contains(r))
+            );
+        }
+        assert!(run_query(&tree, "nearest", "4").is_err());
+    }
+
+    #[test]
+    fn section_range_algebra() {
+        let a = range(2, 6);
+        let b = range(4, 8);
+
+        assert_eq!(a.len(), 5);
+        assert!(a.contains_section(6) && !a.contains_section(7));
+        assert!(range(2, 8).contains(&range(3, 7)));
+        assert_eq!(a.intersection(&b), Some(range(4, 6)));
+        assert_eq!(a.intersection(&range(7, 9)), None);
+        assert_eq!(b.union(&a), vec![range(2, 8)]);
+        assert_eq!(a.union(&range(7, 9)), vec![range(2, 9)]);
+        assert_eq!(a.union(&range(8, 9)), vec![range(2, 6), range(8, 9)]);
+        assert_eq!(a.difference(&b), vec![range(2, 3)]);
+        assert_eq!(
+            range(2, 8).difference(&range(4, 5)),
+            vec![range(2, 3), range(6, 8)]
+        );
+        assert_eq!(range(4, 5).difference(&range(2, 8)), vec![]);
+        assert_eq!(a.difference(&range(8, 9)), vec![a]);
+
+        let max = usize::MAX;
+        assert_eq!(range(5, max).union(&range(6, max)), vec![range(5, max)]);
+        assert_eq!(range(5, 6).union(&range(7, max)), vec![range(5, max)]);
+        assert_eq!(range(0, max).len(), max);
+        assert_eq!(range(1, max).len(), max);
+    }
 }
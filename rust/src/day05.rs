@@ -1,37 +1,292 @@
 use itertools::Itertools;
 use recap::Recap;
 use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::time::Instant;
 
-type Stack = Vec<char>;
+type Crate = String;
+type Stack = Vec<Crate>;
 
-#[derive(PartialEq, Debug)]
+// shown in the top-of-stacks output for a stack with nothing on it
+const EMPTY_STACK: &str = "_";
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 struct Stacks {
     stacks: Vec<Stack>,
 }
 
+#[derive(PartialEq, Debug)]
+enum MoveError {
+    NoSuchStack {
+        step: usize,
+        stack: usize,
+        stacks: usize,
+    },
+    NotEnoughCrates {
+        step: usize,
+        stack: usize,
+        height: usize,
+        wanted: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NoSuchStack {
+                step,
+                stack,
+                stacks,
+            } => write!(
+                f,
+                "move {}: there is no stack {}, only 1 to {}",
+                step, stack, stacks
+            ),
+            MoveError::NotEnoughCrates {
+                step,
+                stack,
+                height,
+                wanted,
+            } => write!(
+                f,
+                "move {}: stack {} holds {} crates, cannot move {}",
+                step, stack, height, wanted
+            ),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+// whether move number `step` can be made on `stacks` stacks, `height` giving the number of
+// crates on a (1-based) stack
+fn check_move(
+    step: usize,
+    d: &Direction,
+    stacks: usize,
+    height: impl Fn(usize) -> usize,
+) -> Result<(), MoveError> {
+    for stack in [d.from, d.to] {
+        if stack == 0 || stack > stacks {
+            return Err(MoveError::NoSuchStack {
+                step,
+                stack,
+                stacks,
+            });
+        }
+    }
+    let height = height(d.from);
+    if height < d.num_crates {
+        return Err(MoveError::NotEnoughCrates {
+            step,
+            stack: d.from,
+            height,
+            wanted: d.num_crates,
+        });
+    }
+    Ok(())
+}
+
 impl Stacks {
-    fn execute(&mut self, d: Direction) {
+    // check move number `step` before handing it to the crane
+    fn apply(&mut self, crane: &dyn Crane, step: usize, d: &Direction) -> Result<(), MoveError> {
+        check_move(step, d, self.stacks.len(), |i| self.stacks[i - 1].len())?;
+        crane.execute(self, d);
+        Ok(())
+    }
+
+    fn peek_all(&self) -> String {
+        join_tops(
+            self.stacks
+                .iter()
+                .map(|s| s.last().map_or(EMPTY_STACK, |c| c.as_str())),
+        )
+    }
+}
+
+// single character labels are run together as in the puzzle answer, longer ones need commas to
+// tell where one ends and the next begins
+fn join_tops<'a>(tops: impl Iterator<Item = &'a str>) -> String {
+    let tops = tops.collect_vec();
+    if tops.iter().any(|t| t.chars().count() > 1) {
+        tops.join(",")
+    } else {
+        tops.join("")
+    }
+}
+
+// the primitives cranes move crates with, so a crane is written once for vec and chunked stacks.
+// stacks are numbered from 1
+trait CrateStorage {
+    // crates in the crane's hook, bottom first
+    type Crates;
+
+    fn take_top(&mut self, from: usize, n: usize) -> Self::Crates;
+    fn take_bottom(&mut self, from: usize, n: usize) -> Self::Crates;
+    fn put(&mut self, to: usize, crates: Self::Crates);
+}
+
+impl CrateStorage for Stacks {
+    type Crates = Stack;
+
+    fn take_top(&mut self, from: usize, n: usize) -> Stack {
+        let from = &mut self.stacks[from - 1];
+        from.split_off(from.len() - n)
+    }
+
+    fn take_bottom(&mut self, from: usize, n: usize) -> Stack {
+        self.stacks[from - 1].drain(..n).collect()
+    }
+
+    fn put(&mut self, to: usize, crates: Stack) {
+        self.stacks[to - 1].extend(crates);
+    }
+}
+
+trait Crane {
+    fn name(&self) -> String;
+    fn execute(&self, stacks: &mut Stacks, d: &Direction);
+    // the same move on chunked stacks
+    fn execute_chunked(&self, stacks: &mut ChunkedStacks, d: &Direction);
+
+    // whether moving the same number of crates straight back undoes a move
+    fn reversible(&self) -> bool {
+        false
+    }
+}
+
+// a crane built only from the storage primitives. `Crane` has to stay object safe, so this
+// generic form is what each such crane implements and the two entry points forward to it
+trait StorageCrane {
+    fn name(&self) -> String;
+    fn move_crates<S: CrateStorage>(&self, stacks: &mut S, d: &Direction);
+
+    fn reversible(&self) -> bool {
+        false
+    }
+}
+
+impl<T: StorageCrane> Crane for T {
+    fn name(&self) -> String {
+        StorageCrane::name(self)
+    }
+
+    fn execute(&self, stacks: &mut Stacks, d: &Direction) {
+        self.move_crates(stacks, d);
+    }
+
+    fn execute_chunked(&self, stacks: &mut ChunkedStacks, d: &Direction) {
+        self.move_crates(stacks, d);
+    }
+
+    fn reversible(&self) -> bool {
+        StorageCrane::reversible(self)
+    }
+}
+
+// moves one crate at a time
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn reversible(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, stacks: &mut Stacks, d: &Direction) {
         for _ in times(d.num_crates) {
-            let c = self.stacks[d.from - 1].pop().unwrap();
-            self.stacks[d.to - 1].push(c);
+            let c = stacks.stacks[d.from - 1].pop().unwrap();
+            stacks.stacks[d.to - 1].push(c);
         }
     }
 
-    fn execute_two(&mut self, d: Direction) {
-        let from: &mut Stack = &mut self.stacks[d.from - 1];
-        let tail = from.split_off(from.len() - d.num_crates);
-        for c in tail.into_iter() {
-            self.stacks[d.to - 1].push(c);
+    // one crate at a time is the whole lot turned upside down
+    fn execute_chunked(&self, stacks: &mut ChunkedStacks, d: &Direction) {
+        let moved = stacks.take_top(d.from, d.num_crates);
+        stacks.put(d.to, flipped(moved));
+    }
+}
+
+// moves all the crates at once, keeping their order
+struct CrateMover9001;
+
+impl StorageCrane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn reversible(&self) -> bool {
+        true
+    }
+
+    fn move_crates<S: CrateStorage>(&self, stacks: &mut S, d: &Direction) {
+        let moved = stacks.take_top(d.from, d.num_crates);
+        stacks.put(d.to, moved);
+    }
+}
+
+// moves up to `size` crates at a time, keeping the order within each batch
+struct BatchCrane {
+    size: usize,
+}
+
+impl StorageCrane for BatchCrane {
+    fn name(&self) -> String {
+        format!("batch crane ({} at a time)", self.size)
+    }
+
+    fn move_crates<S: CrateStorage>(&self, stacks: &mut S, d: &Direction) {
+        let mut remaining = d.num_crates;
+        while remaining > 0 {
+            let batch = remaining.min(self.size);
+            let crates = stacks.take_top(d.from, batch);
+            stacks.put(d.to, crates);
+            remaining -= batch;
         }
     }
+}
 
-    fn peek_all(&self) -> String {
-        self.stacks.iter().map(|s| s.last().unwrap()).join("")
+// pulls crates one at a time from the bottom of the stack and drops them on top
+struct BottomCrane;
+
+impl StorageCrane for BottomCrane {
+    fn name(&self) -> String {
+        "bottom crane".to_string()
+    }
+
+    fn move_crates<S: CrateStorage>(&self, stacks: &mut S, d: &Direction) {
+        let bottom = stacks.take_bottom(d.from, d.num_crates);
+        stacks.put(d.to, bottom);
+    }
+}
+
+fn crane_by_name(name: &str) -> Result<Box<dyn Crane>, Box<dyn Error>> {
+    match name.split_once(':') {
+        None if name == "9000" => Ok(Box::new(CrateMover9000)),
+        None if name == "9001" => Ok(Box::new(CrateMover9001)),
+        None if name == "bottom" => Ok(Box::new(BottomCrane)),
+        Some(("batch", size)) => match size.parse()? {
+            0 => Err("batch size must be at least 1".into()),
+            size => Ok(Box::new(BatchCrane { size })),
+        },
+        _ => Err(format!(
+            "unknown crane {}, expected 9000, 9001, batch:N or bottom",
+            name
+        )
+        .into()),
     }
 }
 
-#[derive(Debug, Deserialize, Recap, PartialEq)]
+#[derive(Debug, Deserialize, Recap, PartialEq, Clone)]
 #[recap(regex = r#"move (?P<num_crates>\d+) from (?P<from>\d+) to (?P<to>\d+)"#)]
 struct Direction {
     num_crates: usize,
@@ -39,23 +294,129 @@ struct Direction {
     to: usize,
 }
 
-fn parse_stacks(input: &str) -> Stacks {
-    let mut lines = input.lines().rev();
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.num_crates, self.from, self.to
+        )
+    }
+}
+
+// the character offsets spanned by every `[label]` crate on a line, with its label
+fn crates_on_line(line: &str) -> Vec<(RangeInclusive<usize>, Crate)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut crates = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] != '[' {
+            i += 1;
+            continue;
+        }
+        let close = chars[i..]
+            .iter()
+            .position(|&c| c == ']')
+            .map_or(chars.len(), |p| i + p);
+        crates.push((i..=close, chars[i + 1..close].iter().collect()));
+        i = close + 1;
+    }
+    crates
+}
 
-    // the first line is a header with the stack numbers. use it to count the number of stacks.
-    let num_stacks = lines.next().unwrap().as_bytes().chunks(4).count();
-    let mut stacks: Vec<Stack> = vec![vec![]; num_stacks];
+// the character offset of the middle of every stack number in the footer
+fn footer_columns(line: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut columns = vec![];
+    let mut start = None;
 
-    for line in lines {
-        for (i, cr) in line.as_bytes().chunks(4).enumerate() {
-            let c = cr[1] as char;
-            if c != ' ' {
-                stacks[i].push(c);
+    for (i, c) in chars.iter().chain([&' ']).enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                if !chars[s..i].iter().all(|c| c.is_ascii_digit()) {
+                    return Err(
+                        format!("expected stack numbers in the footer, found {:?}", line).into(),
+                    );
+                }
+                columns.push((s + i - 1) / 2);
+                start = None;
             }
+            _ => {}
+        }
+    }
+    if columns.is_empty() {
+        return Err("the drawing has no footer numbering the stacks".into());
+    }
+    Ok(columns)
+}
+
+fn parse_stacks(input: &str) -> Result<Stacks, Box<dyn Error>> {
+    let lines = input.lines().collect_vec();
+    let (footer, rows) = lines
+        .split_last()
+        .ok_or("the drawing has no footer numbering the stacks")?;
+
+    // the last line is a footer with the stack numbers. each crate belongs to the stack whose
+    // number sits under its brackets, so columns can be any width and padding can be ragged.
+    let columns = footer_columns(footer)?;
+    let mut stacks: Vec<Stack> = vec![vec![]; columns.len()];
+
+    for (row, line) in rows.iter().enumerate().rev() {
+        for (span, label) in crates_on_line(line) {
+            let i = columns
+                .iter()
+                .position(|column| span.contains(column))
+                .ok_or_else(|| {
+                    format!(
+                        "crate [{}] on line {} is not above any stack number",
+                        label,
+                        row + 1
+                    )
+                })?;
+            stacks[i].push(label);
         }
     }
 
-    Stacks { stacks }
+    Ok(Stacks { stacks })
+}
+
+// draws the stacks the way the puzzle input does, with a footer numbering each stack. every
+// column is wide enough for the longest label and the largest stack number
+fn render(stacks: &Stacks) -> String {
+    let height = stacks.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let label_width = stacks
+        .stacks
+        .iter()
+        .flatten()
+        .map(|c| c.chars().count())
+        .max()
+        .unwrap_or(1);
+    let width = (label_width + 2).max(stacks.stacks.len().to_string().len() + 2);
+    let centered = |s: String| {
+        let pad = width - s.chars().count();
+        format!("{}{}{}", " ".repeat(pad / 2), s, " ".repeat(pad - pad / 2))
+    };
+    let mut lines = vec![];
+
+    for row in (0..height).rev() {
+        let line = stacks
+            .stacks
+            .iter()
+            .map(|s| match s.get(row) {
+                Some(c) => centered(format!("[{}]", c)),
+                None => centered(String::new()),
+            })
+            .join(" ");
+        lines.push(line.trim_end().to_string());
+    }
+    let footer = (1..=stacks.stacks.len())
+        .map(|n| centered(n.to_string()))
+        .join(" ");
+    lines.push(footer.trim_end().to_string());
+
+    lines.join("\n")
 }
 
 fn parse_directions(input: &str) -> Result<Vec<Direction>, Box<dyn Error>> {
@@ -70,25 +431,833 @@ fn parse_directions(input: &str) -> Result<Vec<Direction>, Box<dyn Error>> {
 fn parse(input: &str) -> (Stacks, Vec<Direction>) {
     let (stacks, directions) = input.split_once("\n\n").unwrap();
 
-    (parse_stacks(stacks), parse_directions(directions).unwrap())
+    (
+        parse_stacks(stacks).unwrap(),
+        parse_directions(directions).unwrap(),
+    )
 }
 
-fn part_one(stacks: Stacks, directions: Vec<Direction>) -> String {
-    let mut stacks = stacks;
+// one applied move with the source and destination stacks as they were before and after it
+#[derive(Debug)]
+struct JournalEntry {
+    step: usize,
+    direction: Direction,
+    before: (Stack, Stack),
+    after: (Stack, Stack),
+    // the crates that landed on the destination stack, bottom first
+    moved: Stack,
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = &self.direction;
+        write!(
+            f,
+            "step {}: moved [{}] from {} ({} -> {}) to {} ({} -> {})",
+            self.step,
+            self.moved.iter().join("] ["),
+            d.from,
+            self.before.0.len(),
+            self.after.0.len(),
+            d.to,
+            self.before.1.len(),
+            self.after.1.len()
+        )
+    }
+}
+
+// steps through a program, recording every move so it can be undone and redone
+struct Journal<'a> {
+    stacks: Stacks,
+    crane: &'a dyn Crane,
+    directions: Vec<Direction>,
+    entries: Vec<JournalEntry>,
+    // how many moves are currently applied
+    position: usize,
+}
+
+impl<'a> Journal<'a> {
+    fn new(stacks: Stacks, directions: Vec<Direction>, crane: &'a dyn Crane) -> Self {
+        Journal {
+            stacks,
+            crane,
+            directions,
+            entries: vec![],
+            position: 0,
+        }
+    }
+
+    fn restore(&mut self, from: usize, to: usize, stacks: (Stack, Stack)) {
+        self.stacks.stacks[from - 1] = stacks.0;
+        self.stacks.stacks[to - 1] = stacks.1;
+    }
+
+    // applies the next move, replaying the journal if it was undone. None at the end
+    fn forward(&mut self) -> Result<Option<&JournalEntry>, MoveError> {
+        if self.position == self.directions.len() {
+            return Ok(None);
+        }
+        if self.position == self.entries.len() {
+            let d = self.directions[self.position].clone();
+            let step = self.position + 1;
+            // only the two stacks the move touches are copied, once they are known to exist
+            let stacks = &self.stacks.stacks;
+            check_move(step, &d, stacks.len(), |i| stacks[i - 1].len())?;
+            let touched = |stacks: &Stacks| {
+                (
+                    stacks.stacks[d.from - 1].clone(),
+                    stacks.stacks[d.to - 1].clone(),
+                )
+            };
+            let before = touched(&self.stacks);
+            self.stacks.apply(self.crane, step, &d)?;
+            let after = touched(&self.stacks);
+            // every crane puts the moved crates on top of the destination, which also holds
+            // when the source and destination are the same stack
+            let moved = after.1[after.1.len() - d.num_crates..].to_vec();
+            self.entries.push(JournalEntry {
+                step,
+                direction: d,
+                before,
+                after,
+                moved,
+            });
+        } else {
+            let entry = &self.entries[self.position];
+            let (from, to) = (entry.direction.from, entry.direction.to);
+            let after = entry.after.clone();
+            self.restore(from, to, after);
+        }
+        self.position += 1;
+        Ok(Some(&self.entries[self.position - 1]))
+    }
+
+    // takes back the last applied move. None at the start
+    fn back(&mut self) -> Option<&JournalEntry> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        let entry = &self.entries[self.position];
+        let (from, to) = (entry.direction.from, entry.direction.to);
+        let before = entry.before.clone();
+        self.restore(from, to, before);
+        Some(&self.entries[self.position])
+    }
+
+    fn undo(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.back().is_some()).count()
+    }
+
+    fn redo(&mut self, n: usize) -> Result<usize, MoveError> {
+        for done in 0..n {
+            if self.forward()?.is_none() {
+                return Ok(done);
+            }
+        }
+        Ok(n)
+    }
+
+    // moves to the state after `step` moves, stopping at the end of the program
+    fn jump(&mut self, step: usize) -> Result<(), MoveError> {
+        if step < self.position {
+            self.undo(self.position - step);
+        } else {
+            self.redo(step - self.position)?;
+        }
+        Ok(())
+    }
+}
+
+// reads `next [n]`, `undo [n]`, `jump n`, `show` and `quit` commands from stdin. a bad command
+// or a move that can't be made is reported and the session carries on
+fn debug(journal: &mut Journal) -> Result<(), Box<dyn Error>> {
+    let stdin = io::stdin();
+    loop {
+        print!("step {}/{}> ", journal.position, journal.directions.len());
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("next");
+        let count: usize = match words.next().map_or(Ok(1), |w| w.parse()) {
+            Ok(count) => count,
+            Err(e) => {
+                println!("{}: {}", command, e);
+                continue;
+            }
+        };
+        match command {
+            "n" | "next" => {
+                for _ in 0..count {
+                    match journal.forward() {
+                        Ok(Some(entry)) => println!("{}", entry),
+                        Ok(None) => break,
+                        Err(e) => {
+                            println!("{}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+            "u" | "undo" => {
+                for _ in 0..count {
+                    match journal.back() {
+                        Some(entry) => println!("undid {}", entry),
+                        None => break,
+                    }
+                }
+            }
+            "j" | "jump" => {
+                if let Err(e) = journal.jump(count) {
+                    println!("{}", e);
+                }
+            }
+            "s" | "show" => println!("{}", render(&journal.stacks)),
+            "q" | "quit" => return Ok(()),
+            _ => println!("commands: next [n], undo [n], jump n, show, quit"),
+        }
+    }
+}
+
+// what a plan has to reach: an exact arrangement, or just the crate on top of each stack
+// (None for a stack that must end up empty)
+enum Goal {
+    Arrangement(Stacks),
+    Tops(Vec<Option<Crate>>),
+}
+
+impl Goal {
+    // one label per stack, with EMPTY_STACK for a stack that must end up empty. labels are
+    // separated by commas when any is longer than one character, as `Stacks::peek_all` writes
+    fn tops(labels: &str) -> Self {
+        let labels: Vec<String> = if labels.contains(',') {
+            labels.split(',').map(String::from).collect()
+        } else {
+            labels.chars().map(String::from).collect()
+        };
+        Goal::Tops(
+            labels
+                .into_iter()
+                .map(|c| if c == EMPTY_STACK { None } else { Some(c) })
+                .collect(),
+        )
+    }
+
+    fn reached(&self, stacks: &Stacks) -> bool {
+        match self {
+            Goal::Arrangement(target) => stacks == target,
+            Goal::Tops(tops) => stacks
+                .stacks
+                .iter()
+                .zip(tops)
+                .all(|(s, top)| s.last() == top.as_ref()),
+        }
+    }
+
+    // how far from the goal a state looks: crates not yet resting on a finished base, or for
+    // every stack showing the wrong top, how buried the crate that belongs there is
+    fn estimate(&self, stacks: &Stacks) -> usize {
+        match self {
+            Goal::Arrangement(target) => stacks
+                .stacks
+                .iter()
+                .zip(&target.stacks)
+                .map(|(s, t)| s.len() - s.iter().zip(t).take_while(|(a, b)| a == b).count())
+                .sum(),
+            Goal::Tops(tops) => {
+                let finished = |j: usize| stacks.stacks[j].last() == tops[j].as_ref();
+                let mut estimate = 0;
+                let mut wanted = HashMap::new();
+                for (i, top) in tops.iter().enumerate().filter(|&(i, _)| !finished(i)) {
+                    match top {
+                        None => estimate += stacks.stacks[i].len(),
+                        Some(top) => *wanted.entry(top).or_insert(0) += 1,
+                    }
+                }
+                // every stack still wanting a crate takes the least buried free copy, where
+                // taking one from under (or off) a finished stack costs two more moves to repair
+                for (top, count) in wanted {
+                    let costs = stacks.stacks.iter().enumerate().flat_map(|(j, s)| {
+                        let repair = if finished(j) { 2 } else { 0 };
+                        s.iter()
+                            .rev()
+                            .enumerate()
+                            .filter(move |(_, c)| *c == top)
+                            .map(move |(above, _)| 1 + above + repair)
+                    });
+                    estimate += costs.sorted().take(count).sum::<usize>();
+                }
+                estimate
+            }
+        }
+    }
+
+    fn check(&self, start: &Stacks) -> Result<(), Box<dyn Error>> {
+        let stacks = match self {
+            Goal::Arrangement(target) => {
+                let sorted = |s: &Stacks| s.stacks.iter().flatten().sorted().cloned().collect_vec();
+                if sorted(start) != sorted(target) {
+                    return Err("the target arrangement has different crates".into());
+                }
+                target.stacks.len()
+            }
+            Goal::Tops(tops) => tops.len(),
+        };
+        if stacks != start.stacks.len() {
+            return Err(format!(
+                "the goal has {} stacks, the start has {}",
+                stacks,
+                start.stacks.len()
+            )
+            .into());
+        }
+        if let Goal::Tops(tops) = self {
+            let available = start.stacks.iter().flatten().counts();
+            for (label, wanted) in tops.iter().flatten().counts() {
+                if available.get(label).copied().unwrap_or(0) < wanted {
+                    return Err(
+                        format!("the goal needs {} crates labelled {}", wanted, label).into(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// every move the crane could make next, with the state it leads to
+fn successors<'a>(
+    stacks: &'a Stacks,
+    crane: &'a dyn Crane,
+) -> impl Iterator<Item = (Direction, Stacks)> + 'a {
+    let n = stacks.stacks.len();
+    (1..=n)
+        .cartesian_product(1..=n)
+        .filter(|(from, to)| from != to)
+        .flat_map(move |(from, to)| {
+            (1..=stacks.stacks[from - 1].len()).map(move |num_crates| Direction {
+                num_crates,
+                from,
+                to,
+            })
+        })
+        .map(move |d| {
+            let mut next = stacks.clone();
+            crane.execute(&mut next, &d);
+            (d, next)
+        })
+}
+
+// search states in the order a plan would reach them, each remembering how it was reached.
+// states are stored packed, one number per crate and a marker between stacks, which keeps
+// memory small on the full puzzle input
+struct SearchTree {
+    labels: Vec<Crate>,
+    ids: HashMap<Crate, u16>,
+    nodes: Vec<(Packed, Option<(usize, Direction)>)>,
+    seen: HashSet<Packed>,
+}
+
+type Packed = Rc<[u16]>;
+
+const END_OF_STACK: u16 = u16::MAX;
+
+impl SearchTree {
+    fn new(start: &Stacks) -> Self {
+        let labels: Vec<Crate> = start.stacks.iter().flatten().unique().cloned().collect();
+        let ids = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| (label.clone(), i as u16))
+            .collect();
+        let mut tree = SearchTree {
+            labels,
+            ids,
+            nodes: vec![],
+            seen: HashSet::new(),
+        };
+        let packed = tree.pack(start);
+        tree.seen.insert(packed.clone());
+        tree.nodes.push((packed, None));
+        tree
+    }
+
+    fn pack(&self, stacks: &Stacks) -> Packed {
+        stacks
+            .stacks
+            .iter()
+            .flat_map(|s| s.iter().map(|c| self.ids[c]).chain([END_OF_STACK]))
+            .collect()
+    }
+
+    fn state(&self, node: usize) -> Stacks {
+        let packed = &self.nodes[node].0;
+        Stacks {
+            stacks: packed
+                .split(|id| *id == END_OF_STACK)
+                .take(packed.iter().filter(|id| **id == END_OF_STACK).count())
+                .map(|s| {
+                    s.iter()
+                        .map(|id| self.labels[*id as usize].clone())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    // adds a state if it is new, returning its index
+    fn add(&mut self, stacks: &Stacks, parent: usize, d: Direction) -> Option<usize> {
+        let packed = self.pack(stacks);
+        if !self.seen.insert(packed.clone()) {
+            return None;
+        }
+        self.nodes.push((packed, Some((parent, d))));
+        Some(self.nodes.len() - 1)
+    }
+
+    fn program(&self, mut node: usize) -> Vec<Direction> {
+        let mut program = vec![];
+        while let Some((parent, d)) = &self.nodes[node].1 {
+            program.push(d.clone());
+            node = *parent;
+        }
+        program.reverse();
+        program
+    }
+}
+
+enum Search {
+    Found(Vec<Direction>, Stacks),
+    Unreachable,
+    TooLarge,
+}
+
+// breadth first, so the first state found that is `done` is reached by a shortest program
+fn shortest_plan(
+    start: &Stacks,
+    done: impl Fn(&Stacks) -> bool,
+    crane: &dyn Crane,
+    max_states: usize,
+) -> Search {
+    if done(start) {
+        return Search::Found(vec![], start.clone());
+    }
+    let mut tree = SearchTree::new(start);
+    let mut next = 0;
+
+    while next < tree.nodes.len() {
+        let current = tree.state(next);
+        for (d, stacks) in successors(&current, crane) {
+            if let Some(added) = tree.add(&stacks, next, d) {
+                if done(&stacks) {
+                    return Search::Found(tree.program(added), stacks);
+                }
+            }
+        }
+        if tree.nodes.len() > max_states {
+            return Search::TooLarge;
+        }
+        next += 1;
+    }
+    Search::Unreachable
+}
+
+// greedy best first: always expand the state that looks closest to the goal, preferring the
+// shallowest on ties
+fn greedy_plan(
+    start: &Stacks,
+    goal: &Goal,
+    crane: &dyn Crane,
+    max_states: usize,
+) -> Option<Vec<Direction>> {
+    let mut tree = SearchTree::new(start);
+    let mut queue = BinaryHeap::from([(Reverse(goal.estimate(start)), Reverse(0), 0)]);
+
+    while let Some((_, Reverse(depth), node)) = queue.pop() {
+        let current = tree.state(node);
+        for (d, stacks) in successors(&current, crane) {
+            if let Some(added) = tree.add(&stacks, node, d) {
+                if goal.reached(&stacks) {
+                    return Some(tree.program(added));
+                }
+                queue.push((Reverse(goal.estimate(&stacks)), Reverse(depth + 1), added));
+            }
+        }
+        if tree.nodes.len() > max_states {
+            return None;
+        }
+    }
+    None
+}
+
+// enforced hill climbing: search breadth first only until some state looks closer to the goal,
+// commit to it and start again from there. each search stays small, the total is not optimal
+fn hill_climbing_plan(
+    start: &Stacks,
+    goal: &Goal,
+    crane: &dyn Crane,
+    max_states: usize,
+) -> Option<Vec<Direction>> {
+    let mut state = start.clone();
+    let mut program = vec![];
+
+    while !goal.reached(&state) {
+        let estimate = goal.estimate(&state);
+        match shortest_plan(&state, |s| goal.estimate(s) < estimate, crane, max_states) {
+            Search::Found(steps, next) => {
+                program.extend(steps);
+                state = next;
+            }
+            _ => return None,
+        }
+    }
+    Some(program)
+}
+
+// a shortest program when the search space is small enough, otherwise a heuristic one
+fn plan(
+    start: &Stacks,
+    goal: &Goal,
+    crane: &dyn Crane,
+) -> Result<Option<Vec<Direction>>, Box<dyn Error>> {
+    goal.check(start)?;
+    Ok(
+        match shortest_plan(start, |s| goal.reached(s), crane, 200_000) {
+            Search::Found(program, _) => Some(program),
+            Search::Unreachable => None,
+            Search::TooLarge => hill_climbing_plan(start, goal, crane, 500_000)
+                .or_else(|| greedy_plan(start, goal, crane, 1_000_000)),
+        },
+    )
+}
+
+// a run of crates shared between stacks: `crates[start..end]` read bottom to top, or top to
+// bottom when `reversed`. splitting and reversing runs never copies a crate
+#[derive(Debug, Clone)]
+struct Segment {
+    crates: Rc<[Crate]>,
+    start: usize,
+    end: usize,
+    reversed: bool,
+}
+
+impl Segment {
+    fn new(crates: Stack) -> Self {
+        Segment {
+            end: crates.len(),
+            crates: crates.into(),
+            start: 0,
+            reversed: false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn top(&self) -> &Crate {
+        match self.reversed {
+            false => &self.crates[self.end - 1],
+            true => &self.crates[self.start],
+        }
+    }
+
+    // splits off the top `n` crates, leaving the rest in `self`
+    fn split_top(&mut self, n: usize) -> Segment {
+        let mut top = self.clone();
+        if self.reversed {
+            top.end = self.start + n;
+            self.start += n;
+        } else {
+            top.start = self.end - n;
+            self.end -= n;
+        }
+        top
+    }
+
+    // splits off the bottom `n` crates, leaving the rest in `self`
+    fn split_bottom(&mut self, n: usize) -> Segment {
+        let mut bottom = self.clone();
+        if self.reversed {
+            bottom.start = self.end - n;
+            self.end -= n;
+        } else {
+            bottom.end = self.start + n;
+            self.start += n;
+        }
+        bottom
+    }
 
-    for d in directions {
-        stacks.execute(d);
+    fn iter(&self) -> Box<dyn Iterator<Item = &Crate> + '_> {
+        let crates = self.crates[self.start..self.end].iter();
+        match self.reversed {
+            false => Box::new(crates),
+            true => Box::new(crates.rev()),
+        }
+    }
+}
+
+// the same segments upside down
+fn flipped(mut segments: Vec<Segment>) -> Vec<Segment> {
+    segments.reverse();
+    for segment in &mut segments {
+        segment.reversed = !segment.reversed;
+    }
+    segments
+}
+
+// once a stack is cut into this many segments it is copied back into one
+const MAX_SEGMENTS: usize = 4096;
+
+// a stack kept as segments, bottom first, so moving n crates touches segments instead of crates
+#[derive(Debug, Default)]
+struct ChunkedStack {
+    segments: Vec<Segment>,
+    len: usize,
+}
+
+impl ChunkedStack {
+    fn new(stack: &Stack) -> Self {
+        let mut chunked = ChunkedStack::default();
+        chunked.put(vec![Segment::new(stack.clone())]);
+        chunked
+    }
+
+    fn to_stack(&self) -> Stack {
+        self.segments
+            .iter()
+            .flat_map(|s| s.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn top(&self) -> Option<&Crate> {
+        self.segments.last().map(|s| s.top())
+    }
+
+    // the top `n` crates as segments, bottom first
+    fn take_top(&mut self, n: usize) -> Vec<Segment> {
+        let mut taken = vec![];
+        let mut remaining = n;
+        while remaining > 0 {
+            let last = self.segments.last_mut().unwrap();
+            if last.len() <= remaining {
+                remaining -= last.len();
+                taken.push(self.segments.pop().unwrap());
+            } else {
+                taken.push(last.split_top(remaining));
+                remaining = 0;
+            }
+        }
+        self.len -= n;
+        taken.reverse();
+        taken
+    }
+
+    // the bottom `n` crates as segments, bottom first
+    fn take_bottom(&mut self, n: usize) -> Vec<Segment> {
+        let whole = self
+            .segments
+            .iter()
+            .scan(0, |total, s| {
+                *total += s.len();
+                Some(*total)
+            })
+            .take_while(|total| *total <= n)
+            .count();
+        let mut taken: Vec<Segment> = self.segments.drain(..whole).collect();
+        let remaining = n - taken.iter().map(|s| s.len()).sum::<usize>();
+        if remaining > 0 {
+            taken.push(self.segments[0].split_bottom(remaining));
+        }
+        self.len -= n;
+        taken
+    }
+
+    fn put(&mut self, segments: Vec<Segment>) {
+        self.len += segments.iter().map(|s| s.len()).sum::<usize>();
+        self.segments
+            .extend(segments.into_iter().filter(|s| s.len() > 0));
+        if self.segments.len() > MAX_SEGMENTS {
+            self.segments = vec![Segment::new(self.to_stack())];
+        }
     }
-    stacks.peek_all()
 }
 
-fn part_two(stacks: Stacks, directions: Vec<Direction>) -> String {
+// the same crates as `Stacks`, for long programs on very tall stacks
+struct ChunkedStacks {
+    stacks: Vec<ChunkedStack>,
+}
+
+impl ChunkedStacks {
+    fn new(stacks: &Stacks) -> Self {
+        ChunkedStacks {
+            stacks: stacks.stacks.iter().map(ChunkedStack::new).collect(),
+        }
+    }
+
+    fn apply(&mut self, crane: &dyn Crane, step: usize, d: &Direction) -> Result<(), MoveError> {
+        check_move(step, d, self.stacks.len(), |i| self.stacks[i - 1].len)?;
+        crane.execute_chunked(self, d);
+        Ok(())
+    }
+
+    fn to_stacks(&self) -> Stacks {
+        Stacks {
+            stacks: self.stacks.iter().map(|s| s.to_stack()).collect(),
+        }
+    }
+
+    fn peek_all(&self) -> String {
+        join_tops(
+            self.stacks
+                .iter()
+                .map(|s| s.top().map_or(EMPTY_STACK, |c| c.as_str())),
+        )
+    }
+}
+
+impl CrateStorage for ChunkedStacks {
+    type Crates = Vec<Segment>;
+
+    fn take_top(&mut self, from: usize, n: usize) -> Vec<Segment> {
+        self.stacks[from - 1].take_top(n)
+    }
+
+    fn take_bottom(&mut self, from: usize, n: usize) -> Vec<Segment> {
+        self.stacks[from - 1].take_bottom(n)
+    }
+
+    fn put(&mut self, to: usize, segments: Vec<Segment>) {
+        self.stacks[to - 1].put(segments);
+    }
+}
+
+fn run_chunked(
+    stacks: &Stacks,
+    directions: &[Direction],
+    crane: &dyn Crane,
+) -> Result<ChunkedStacks, MoveError> {
+    let mut chunked = ChunkedStacks::new(stacks);
+    for (i, d) in directions.iter().enumerate() {
+        chunked.apply(crane, i + 1, d)?;
+    }
+    Ok(chunked)
+}
+
+// tall stacks and a long program of valid moves, each taking a varying share of its stack
+fn generate_input(num_stacks: usize, height: usize, moves: usize) -> (Stacks, Vec<Direction>) {
+    let stacks: Vec<Stack> = (0..num_stacks)
+        .map(|s| {
+            (0..height)
+                .map(|i| ((b'A' + ((s + i * 7) % 26) as u8) as char).to_string())
+                .collect()
+        })
+        .collect();
+    let mut heights = vec![height; num_stacks];
+    let mut directions = vec![];
+    for i in 0..moves {
+        let from = i % num_stacks;
+        let to = (from + 1 + i / num_stacks % (num_stacks - 1)) % num_stacks;
+        let num_crates = heights[from] * (i % 10) / 10;
+        heights[from] -= num_crates;
+        heights[to] += num_crates;
+        directions.push(Direction {
+            num_crates,
+            from: from + 1,
+            to: to + 1,
+        });
+    }
+    (Stacks { stacks }, directions)
+}
+
+fn bench(moves: usize) -> Result<(), Box<dyn Error>> {
+    let (stacks, directions) = generate_input(9, 100_000, moves);
+    println!("{} moves over 9 stacks of 100000 crates", moves);
+
+    for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+        let start = Instant::now();
+        let mut vec_stacks = stacks.clone();
+        for (i, d) in directions.iter().enumerate() {
+            vec_stacks.apply(crane, i + 1, d)?;
+        }
+        let vec_time = start.elapsed();
+
+        let start = Instant::now();
+        let chunked = run_chunked(&stacks, &directions, crane)?;
+        let chunked_time = start.elapsed();
+
+        if chunked.to_stacks() != vec_stacks {
+            return Err(format!("the two {} runs disagree", crane.name()).into());
+        }
+        println!(
+            "{}: vec stacks {:?}, chunked stacks {:?}",
+            crane.name(),
+            vec_time,
+            chunked_time
+        );
+    }
+    Ok(())
+}
+
+// works out the starting stacks from the final ones by running the program backwards, moving
+// each lot of crates back where it came from, then replays it forwards to check
+fn reconstruct(
+    final_stacks: &Stacks,
+    directions: &[Direction],
+    crane: &dyn Crane,
+) -> Result<Stacks, Box<dyn Error>> {
+    if !crane.reversible() {
+        return Err(format!("moves made by the {} cannot be undone", crane.name()).into());
+    }
+    let mut stacks = final_stacks.clone();
+    for (i, d) in directions.iter().enumerate().rev() {
+        let back = Direction {
+            num_crates: d.num_crates,
+            from: d.to,
+            to: d.from,
+        };
+        stacks.apply(crane, i + 1, &back)?;
+    }
+
+    let mut replayed = stacks.clone();
+    for (i, d) in directions.iter().enumerate() {
+        replayed.apply(crane, i + 1, d)?;
+    }
+    if &replayed != final_stacks {
+        return Err("replaying the reconstructed stacks does not give the final stacks".into());
+    }
+    Ok(stacks)
+}
+
+// runs every move, returning the top crates and, when skipping invalid moves, what was skipped
+fn run(
+    stacks: Stacks,
+    directions: Vec<Direction>,
+    crane: &dyn Crane,
+    skip_invalid: bool,
+) -> Result<(String, Vec<MoveError>), MoveError> {
     let mut stacks = stacks;
+    let mut skipped = vec![];
 
-    for d in directions {
-        stacks.execute_two(d);
+    for (i, d) in directions.iter().enumerate() {
+        match stacks.apply(crane, i + 1, d) {
+            Err(e) if skip_invalid => skipped.push(e),
+            result => result?,
+        }
     }
-    stacks.peek_all()
+    Ok((stacks.peek_all(), skipped))
+}
+
+fn part_one(stacks: Stacks, directions: Vec<Direction>) -> Result<String, MoveError> {
+    Ok(run_chunked(&stacks, &directions, &CrateMover9000)?.peek_all())
+}
+
+fn part_two(stacks: Stacks, directions: Vec<Direction>) -> Result<String, MoveError> {
+    Ok(run_chunked(&stacks, &directions, &CrateMover9001)?.peek_all())
 }
 
 fn times(n: usize) -> impl Iterator {
@@ -96,17 +1265,96 @@ fn times(n: usize) -> impl Iterator {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|a| a == "--bench") {
+        let moves = match args.get(i + 1) {
+            Some(moves) => moves.parse()?,
+            None => 5_000,
+        };
+        return bench(moves);
+    }
+
     let input = include_str!("../input/day05.txt");
     let (stacks, directions) = parse(input);
 
-    let part_one = part_one(stacks, directions);
+    let part_one = part_one(stacks, directions)?;
     println!("Top of stacks (part 1): {}", part_one);
 
     let (stacks, directions) = parse(input);
 
-    let part_two = part_two(stacks, directions);
+    let part_two = part_two(stacks, directions)?;
     println!("Top of stacks (part 2): {}", part_two);
 
+    let crane: Box<dyn Crane> = match args.iter().position(|a| a == "--crane") {
+        Some(i) => {
+            let crane = crane_by_name(args.get(i + 1).ok_or("--crane needs a model")?)?;
+            let (stacks, directions) = parse(input);
+            let skip_invalid = args.iter().any(|a| a == "--skip-invalid");
+            let (top, skipped) = run(stacks, directions, crane.as_ref(), skip_invalid)?;
+            println!("Top of stacks ({}): {}", crane.name(), top);
+            for e in skipped {
+                println!("  skipped {}", e);
+            }
+            crane
+        }
+        None => Box::new(CrateMover9000),
+    };
+
+    let (stacks, directions) = parse(input);
+    let mut journal = Journal::new(stacks, directions, crane.as_ref());
+
+    if args.iter().any(|a| a == "--journal") {
+        while let Some(entry) = journal.forward()? {
+            println!("{}", entry);
+        }
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--dump") {
+        let steps: usize = args
+            .get(i + 1)
+            .ok_or("--dump needs a move count")?
+            .parse()?;
+        journal.jump(steps)?;
+        println!("{}", render(&journal.stacks));
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--plan") {
+        let target = args
+            .get(i + 1)
+            .ok_or("--plan needs tops:<crates> or drawing:<file>")?;
+        let goal = match target.split_once(':') {
+            Some(("tops", tops)) => Goal::tops(tops),
+            Some(("drawing", path)) => {
+                Goal::Arrangement(parse_stacks(std::fs::read_to_string(path)?.trim_end())?)
+            }
+            _ => return Err("--plan needs tops:<crates> or drawing:<file>".into()),
+        };
+        let (stacks, _) = parse(input);
+        match plan(&stacks, &goal, crane.as_ref())? {
+            Some(program) => {
+                for d in &program {
+                    println!("{}", d);
+                }
+                println!("{} moves with the {}", program.len(), crane.name());
+            }
+            None => println!("No plan found for {} with the {}", target, crane.name()),
+        }
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--reconstruct") {
+        let path = args
+            .get(i + 1)
+            .ok_or("--reconstruct needs a drawing file")?;
+        let final_stacks = parse_stacks(std::fs::read_to_string(path)?.trim_end())?;
+        let (_, directions) = parse(input);
+        let stacks = reconstruct(&final_stacks, &directions, crane.as_ref())?;
+        println!("{}", render(&stacks));
+    }
+
+    if args.iter().any(|a| a == "--debug") {
+        debug(&mut journal)?;
+    }
+
     Ok(())
 }
 
@@ -114,9 +1362,13 @@ fn main() -> Result<(), Box<dyn Error>> {
 mod tests {
     use super::*;
 
+    fn stack(labels: &str) -> Stack {
+        labels.chars().map(String::from).collect()
+    }
+
     fn stacks_fixture() -> Stacks {
         Stacks {
-            stacks: vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']],
+            stacks: vec![stack("ZN"), stack("MCD"), stack("P")],
         }
     }
 
@@ -159,7 +1411,7 @@ mod tests {
         let stacks = stacks_fixture();
         let directions = directions_fixture();
 
-        let result = part_one(stacks, directions);
+        let result = part_one(stacks, directions).unwrap();
 
         assert_eq!(result, "CMZ")
     }
@@ -169,8 +1421,329 @@ mod tests {
         let stacks = stacks_fixture();
         let directions = directions_fixture();
 
-        let result = part_two(stacks, directions);
+        let result = part_two(stacks, directions).unwrap();
 
         assert_eq!(result, "MCD");
     }
+
+    #[test]
+    fn other_cranes_test() {
+        let crane = crane_by_name("batch:2").unwrap();
+        let result = run(
+            stacks_fixture(),
+            directions_fixture(),
+            crane.as_ref(),
+            false,
+        )
+        .unwrap()
+        .0;
+        assert_eq!(result, "MCZ");
+
+        let crane = crane_by_name("bottom").unwrap();
+        let result = run(
+            stacks_fixture(),
+            directions_fixture(),
+            crane.as_ref(),
+            false,
+        )
+        .unwrap()
+        .0;
+        assert_eq!(result, "DCM");
+
+        // batches of one behave like the 9000, one large batch like the 9001
+        let crane = BatchCrane { size: 1 };
+        assert_eq!(
+            run(stacks_fixture(), directions_fixture(), &crane, false)
+                .unwrap()
+                .0,
+            "CMZ"
+        );
+        let crane = BatchCrane { size: 3 };
+        assert_eq!(
+            run(stacks_fixture(), directions_fixture(), &crane, false)
+                .unwrap()
+                .0,
+            "MCD"
+        );
+
+        assert!(crane_by_name("batch:0").is_err());
+        assert!(crane_by_name("9002").is_err());
+    }
+
+    #[test]
+    fn invalid_moves_test() {
+        let mut directions = directions_fixture();
+        directions.insert(
+            1,
+            Direction {
+                num_crates: 5,
+                from: 3,
+                to: 1,
+            },
+        );
+        directions.push(Direction {
+            num_crates: 1,
+            from: 0,
+            to: 1,
+        });
+
+        let result = part_one(stacks_fixture(), directions.clone());
+        assert_eq!(
+            result,
+            Err(MoveError::NotEnoughCrates {
+                step: 2,
+                stack: 3,
+                height: 1,
+                wanted: 5
+            })
+        );
+
+        let (top, skipped) = run(stacks_fixture(), directions, &CrateMover9000, true).unwrap();
+        assert_eq!(top, "CMZ");
+        assert_eq!(
+            skipped[1],
+            MoveError::NoSuchStack {
+                step: 6,
+                stack: 0,
+                stacks: 3
+            }
+        );
+        assert_eq!(
+            skipped[1].to_string(),
+            "move 6: there is no stack 0, only 1 to 3"
+        );
+    }
+
+    #[test]
+    fn render_test() {
+        let input = include_str!("../input/day05_test.txt");
+        let (drawing, _) = input.split_once("\n\n").unwrap();
+        assert_eq!(render(&stacks_fixture()), drawing);
+
+        let empty = Stacks {
+            stacks: vec![stack(""), stack("A"), stack("")],
+        };
+        assert_eq!(render(&empty), "    [A]\n 1   2   3");
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let (mut stacks, directions) = parse(include_str!("../input/day05.txt"));
+        let (mut stacks_two, _) = parse(include_str!("../input/day05.txt"));
+
+        for (i, d) in directions.iter().enumerate() {
+            stacks.apply(&CrateMover9000, i + 1, d).unwrap();
+            stacks_two.apply(&CrateMover9001, i + 1, d).unwrap();
+            assert_eq!(parse_stacks(&render(&stacks)).unwrap(), stacks);
+            assert_eq!(parse_stacks(&render(&stacks_two)).unwrap(), stacks_two);
+        }
+    }
+
+    #[test]
+    fn wide_drawings() {
+        let drawing = [
+            format!("[A]{}[K]  ", " ".repeat(23)),
+            format!("[B][C]{}[J] [LL]", " ".repeat(20)),
+            " 1  2  3  4  5  6  7  8  9 10  11 \t".to_string(),
+        ]
+        .join("\n");
+        let stacks = parse_stacks(&drawing).unwrap();
+        assert_eq!(stacks.stacks.len(), 11);
+        assert_eq!(stacks.stacks[0], stack("BA"));
+        assert_eq!(stacks.stacks[1], stack("C"));
+        assert_eq!(stacks.stacks[9], stack("JK"));
+        assert_eq!(stacks.stacks[10], vec!["LL".to_string()]);
+        assert_eq!(stacks.peek_all(), "A,C,_,_,_,_,_,_,_,K,LL");
+
+        let rendered = render(&stacks);
+        assert!(rendered.ends_with("  9    10   11"));
+        assert_eq!(parse_stacks(&rendered).unwrap(), stacks);
+    }
+
+    #[test]
+    fn drawings_need_a_footer_under_every_crate() {
+        assert!(parse_stacks("").is_err());
+        assert!(parse_stacks("[A] [B]").is_err());
+        assert!(parse_stacks("[A] [B]\n   ").is_err());
+        assert_eq!(
+            parse_stacks("[A]     [B]\n 1   2").unwrap_err().to_string(),
+            "crate [B] on line 1 is not above any stack number"
+        );
+    }
+
+    #[test]
+    fn journal_test() {
+        let mut journal = Journal::new(stacks_fixture(), directions_fixture(), &CrateMover9001);
+
+        let entry = journal.forward().unwrap().unwrap();
+        assert_eq!(entry.moved, stack("D"));
+        assert_eq!(
+            entry.to_string(),
+            "step 1: moved [D] from 2 (3 -> 2) to 1 (2 -> 3)"
+        );
+        let entry = journal.forward().unwrap().unwrap();
+        assert_eq!(entry.moved, stack("ZND"));
+
+        assert_eq!(journal.redo(10).unwrap(), 2);
+        assert_eq!(journal.stacks.peek_all(), "MCD");
+        assert!(journal.forward().unwrap().is_none());
+
+        assert_eq!(journal.undo(3), 3);
+        assert_eq!(journal.position, 1);
+        assert_eq!(journal.stacks.stacks[0], stack("ZND"));
+
+        journal.jump(0).unwrap();
+        assert_eq!(journal.stacks, stacks_fixture());
+        assert_eq!(journal.undo(1), 0);
+
+        journal.jump(4).unwrap();
+        assert_eq!(journal.stacks.peek_all(), "MCD");
+
+        let in_place = Direction {
+            num_crates: 1,
+            from: 1,
+            to: 1,
+        };
+        let stacks = Stacks {
+            stacks: vec![stack("AB")],
+        };
+        let mut journal = Journal::new(stacks, vec![in_place], &BottomCrane);
+        let entry = journal.forward().unwrap().unwrap();
+        assert_eq!(entry.moved, stack("A"));
+        assert_eq!(
+            entry.to_string(),
+            "step 1: moved [A] from 1 (2 -> 2) to 1 (2 -> 2)"
+        );
+        assert_eq!(journal.stacks.stacks[0], stack("BA"));
+    }
+
+    #[test]
+    fn plan_test() {
+        let replay = |program: Vec<Direction>, crane: &dyn Crane| {
+            let mut stacks = stacks_fixture();
+            for (i, d) in program.iter().enumerate() {
+                stacks.apply(crane, i + 1, d).unwrap();
+            }
+            stacks
+        };
+
+        let goal = Goal::tops("CMZ");
+        let program = plan(&stacks_fixture(), &goal, &CrateMover9000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(program.len(), 2);
+        assert!(goal.reached(&replay(program, &CrateMover9000)));
+
+        let target = part_two_state();
+        let goal = Goal::Arrangement(target.clone());
+        let program = plan(&stacks_fixture(), &goal, &CrateMover9001)
+            .unwrap()
+            .unwrap();
+        assert!(program.len() <= 4);
+        assert_eq!(replay(program, &CrateMover9001), target);
+
+        let program = hill_climbing_plan(&stacks_fixture(), &goal, &CrateMover9000, 1_000).unwrap();
+        assert_eq!(replay(program, &CrateMover9000), target);
+        let program = greedy_plan(&stacks_fixture(), &goal, &BottomCrane, 1_000).unwrap();
+        assert_eq!(replay(program, &BottomCrane), target);
+
+        assert!(plan(&stacks_fixture(), &Goal::tops("CM"), &CrateMover9000).is_err());
+        assert!(plan(&stacks_fixture(), &Goal::tops("CCZ"), &CrateMover9000).is_err());
+
+        let wide = Stacks {
+            stacks: vec![vec!["AB".to_string()], vec![], vec!["C".to_string()]],
+        };
+        let goal = Goal::tops("_,AB,C");
+        let program = plan(&wide, &goal, &CrateMover9000).unwrap().unwrap();
+        assert_eq!(program.len(), 1);
+        // a single stack can never be rearranged
+        let single = Stacks {
+            stacks: vec![stack("AB")],
+        };
+        let reversed = Goal::Arrangement(Stacks {
+            stacks: vec![stack("BA")],
+        });
+        assert!(plan(&single, &reversed, &CrateMover9001).unwrap().is_none());
+    }
+
+    fn part_two_state() -> Stacks {
+        let mut stacks = stacks_fixture();
+        for (i, d) in directions_fixture().iter().enumerate() {
+            stacks.apply(&CrateMover9001, i + 1, d).unwrap();
+        }
+        stacks
+    }
+
+    #[test]
+    fn chunked_stacks_test() {
+        let (stacks, directions) = generate_input(5, 50, 2_000);
+        let cranes: [&dyn Crane; 4] = [
+            &CrateMover9000,
+            &CrateMover9001,
+            &BatchCrane { size: 3 },
+            &BottomCrane,
+        ];
+
+        for crane in cranes {
+            let mut expected = stacks.clone();
+            let mut chunked = ChunkedStacks::new(&stacks);
+            for (i, d) in directions.iter().enumerate() {
+                expected.apply(crane, i + 1, d).unwrap();
+                chunked.apply(crane, i + 1, d).unwrap();
+            }
+            assert_eq!(chunked.to_stacks(), expected);
+            assert_eq!(chunked.peek_all(), expected.peek_all());
+        }
+
+        // mixing cranes splits reversed segments from both ends
+        let mut expected = stacks.clone();
+        let mut chunked = ChunkedStacks::new(&stacks);
+        for (i, d) in directions.iter().enumerate() {
+            expected.apply(cranes[i % 4], i + 1, d).unwrap();
+            chunked.apply(cranes[i % 4], i + 1, d).unwrap();
+        }
+        assert_eq!(chunked.to_stacks(), expected);
+
+        let mut chunked = ChunkedStacks::new(&stacks_fixture());
+        let d = Direction {
+            num_crates: 2,
+            from: 3,
+            to: 1,
+        };
+        assert_eq!(
+            chunked.apply(&CrateMover9001, 7, &d),
+            Err(MoveError::NotEnoughCrates {
+                step: 7,
+                stack: 3,
+                height: 1,
+                wanted: 2
+            })
+        );
+    }
+
+    #[test]
+    fn reconstruct_test() {
+        let directions = directions_fixture();
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut final_stacks = stacks_fixture();
+            for (i, d) in directions.iter().enumerate() {
+                final_stacks.apply(crane, i + 1, d).unwrap();
+            }
+            let stacks = reconstruct(&final_stacks, &directions, crane).unwrap();
+            assert_eq!(stacks, stacks_fixture());
+        }
+
+        let (stacks, directions) = parse(include_str!("../input/day05.txt"));
+        let final_stacks = run_chunked(&stacks, &directions, &CrateMover9001)
+            .unwrap()
+            .to_stacks();
+        assert_eq!(
+            reconstruct(&final_stacks, &directions, &CrateMover9001).unwrap(),
+            stacks
+        );
+
+        // the fixture's starting stacks are too short to be where the program ends
+        assert!(reconstruct(&stacks_fixture(), &directions_fixture(), &CrateMover9000).is_err());
+        assert!(reconstruct(&stacks_fixture(), &[], &BottomCrane).is_err());
+    }
 }